@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE login_attempts (
+    attempt_key TEXT PRIMARY KEY,
+    failures INT NOT NULL,
+    blocked_until TIMESTAMPTZ,
+    last_failure_at TIMESTAMPTZ NOT NULL
+);
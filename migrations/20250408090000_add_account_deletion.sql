@@ -0,0 +1,38 @@
+-- Add migration script here
+ALTER TABLE users
+ADD COLUMN deletion_scheduled_at TIMESTAMPTZ;
+
+ALTER TABLE users
+ADD COLUMN deletion_cancel_token_hash TEXT;
+
+ALTER TABLE tasks DROP CONSTRAINT fk_user_id;
+
+ALTER TABLE tasks ADD CONSTRAINT fk_user_id FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE recovery_codes DROP CONSTRAINT recovery_codes_user_id_fkey;
+
+ALTER TABLE recovery_codes ADD CONSTRAINT recovery_codes_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE personal_access_tokens DROP CONSTRAINT personal_access_tokens_user_id_fkey;
+
+ALTER TABLE personal_access_tokens ADD CONSTRAINT personal_access_tokens_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE user_identities DROP CONSTRAINT user_identities_user_id_fkey;
+
+ALTER TABLE user_identities ADD CONSTRAINT user_identities_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE magic_link_tokens DROP CONSTRAINT magic_link_tokens_user_id_fkey;
+
+ALTER TABLE magic_link_tokens ADD CONSTRAINT magic_link_tokens_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE webauthn_credentials DROP CONSTRAINT webauthn_credentials_user_id_fkey;
+
+ALTER TABLE webauthn_credentials ADD CONSTRAINT webauthn_credentials_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE webauthn_challenges DROP CONSTRAINT webauthn_challenges_user_id_fkey;
+
+ALTER TABLE webauthn_challenges ADD CONSTRAINT webauthn_challenges_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE sessions DROP CONSTRAINT sessions_user_id_fkey;
+
+ALTER TABLE sessions ADD CONSTRAINT sessions_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
@@ -0,0 +1,15 @@
+-- Add migration script here
+ALTER TABLE users
+ADD COLUMN display_name TEXT;
+
+ALTER TABLE users
+ADD COLUMN avatar BYTEA;
+
+ALTER TABLE users
+ADD COLUMN avatar_content_type TEXT;
+
+ALTER TABLE users
+ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+
+ALTER TABLE users
+ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
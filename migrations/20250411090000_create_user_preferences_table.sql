@@ -0,0 +1,9 @@
+-- Add migration script here
+-- Only the values a user has changed are stored; defaults live in code so they
+-- can change without a migration.
+CREATE TABLE user_preferences (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    preferences JSONB NOT NULL DEFAULT '{}'::jsonb,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
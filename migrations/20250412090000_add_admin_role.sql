@@ -0,0 +1,41 @@
+-- Add migration script here
+-- The first admin has to be promoted by hand:
+--   UPDATE users SET role = 'admin' WHERE email = '...';
+CREATE TYPE user_role AS ENUM ('user', 'admin');
+
+ALTER TABLE users
+    ADD COLUMN role user_role NOT NULL DEFAULT 'user',
+    ADD COLUMN locked_at TIMESTAMPTZ,
+    ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE password_reset_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TYPE admin_action AS ENUM (
+    'verify',
+    'lock',
+    'unlock',
+    'delete',
+    'force_password_reset',
+    'impersonate'
+);
+
+-- Rows outlive both users involved, so the target's email is copied in.
+CREATE TABLE admin_audit_log (
+    id SERIAL PRIMARY KEY,
+    admin_id INT REFERENCES users (id) ON DELETE SET NULL,
+    target_user_id INT REFERENCES users (id) ON DELETE SET NULL,
+    target_email TEXT NOT NULL,
+    action admin_action NOT NULL,
+    ip_address TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX admin_audit_log_created_at_idx ON admin_audit_log (created_at DESC);
@@ -0,0 +1,74 @@
+-- Add migration script here
+CREATE TYPE workspace_role AS ENUM ('owner', 'admin', 'member', 'viewer');
+
+-- Every user has exactly one personal workspace, removed along with them.
+CREATE TABLE workspaces (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    personal_owner_id INT UNIQUE REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE workspace_members (
+    workspace_id INT NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role workspace_role NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (workspace_id, user_id)
+);
+
+CREATE INDEX workspace_members_user_id_idx ON workspace_members (user_id);
+
+CREATE TABLE workspace_invitations (
+    id SERIAL PRIMARY KEY,
+    workspace_id INT NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    role workspace_role NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    invited_by INT REFERENCES users (id) ON DELETE SET NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    accepted_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE projects (
+    id SERIAL PRIMARY KEY,
+    workspace_id INT NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+INSERT INTO workspaces (name, personal_owner_id, created_at)
+SELECT 'Personal', id, NOW()
+FROM users;
+
+INSERT INTO workspace_members (workspace_id, user_id, role, created_at)
+SELECT id, personal_owner_id, 'owner', NOW()
+FROM workspaces
+WHERE personal_owner_id IS NOT NULL;
+
+ALTER TABLE tasks
+    ADD COLUMN workspace_id INT REFERENCES workspaces (id) ON DELETE CASCADE,
+    ADD COLUMN project_id INT REFERENCES projects (id) ON DELETE SET NULL;
+
+UPDATE tasks t
+SET workspace_id = w.id
+FROM workspaces w
+WHERE w.personal_owner_id = t.user_id;
+
+-- Rows from before tasks had an owner go to a workspace of their own, with no
+-- members until an admin hands it to someone.
+WITH unowned AS (
+    INSERT INTO workspaces (name, created_at)
+    SELECT 'Unowned tasks', NOW()
+    WHERE EXISTS (SELECT 1 FROM tasks WHERE workspace_id IS NULL)
+    RETURNING id
+)
+UPDATE tasks t
+SET workspace_id = unowned.id
+FROM unowned
+WHERE t.workspace_id IS NULL;
+
+ALTER TABLE tasks ALTER COLUMN workspace_id SET NOT NULL;
+
+CREATE INDEX tasks_workspace_id_idx ON tasks (workspace_id);
@@ -0,0 +1,21 @@
+-- Add migration script here
+CREATE TYPE share_permission AS ENUM ('view', 'edit');
+
+-- Grants are matched to users by verified email when access is checked, so a
+-- grant for someone without an account starts working once they sign up.
+CREATE TABLE share_grants (
+    id SERIAL PRIMARY KEY,
+    project_id INT REFERENCES projects (id) ON DELETE CASCADE,
+    task_id INT REFERENCES tasks (id) ON DELETE CASCADE,
+    grantee_email TEXT NOT NULL,
+    permission share_permission NOT NULL,
+    granted_by INT REFERENCES users (id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    CHECK (num_nonnulls(project_id, task_id) = 1)
+);
+
+CREATE UNIQUE INDEX share_grants_project_email_idx
+    ON share_grants (project_id, lower(grantee_email)) WHERE project_id IS NOT NULL;
+CREATE UNIQUE INDEX share_grants_task_email_idx
+    ON share_grants (task_id, lower(grantee_email)) WHERE task_id IS NOT NULL;
+CREATE INDEX share_grants_grantee_email_idx ON share_grants (lower(grantee_email));
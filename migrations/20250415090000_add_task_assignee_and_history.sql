@@ -0,0 +1,20 @@
+-- Add migration script here
+ALTER TABLE tasks ADD COLUMN assignee_id INT REFERENCES users (id) ON DELETE SET NULL;
+
+CREATE INDEX tasks_assignee_id_idx ON tasks (assignee_id);
+
+CREATE TYPE task_event AS ENUM ('status_changed', 'assignee_changed');
+
+-- Values are stored as JSON so each event can keep its own shape; the actor
+-- is kept as NULL once their account is gone.
+CREATE TABLE task_history (
+    id SERIAL PRIMARY KEY,
+    task_id INT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    actor_id INT REFERENCES users (id) ON DELETE SET NULL,
+    event task_event NOT NULL,
+    old_value JSONB NOT NULL,
+    new_value JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX task_history_task_id_idx ON task_history (task_id, created_at);
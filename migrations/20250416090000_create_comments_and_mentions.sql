@@ -0,0 +1,35 @@
+-- Add migration script here
+ALTER TABLE tasks
+    ADD COLUMN description TEXT,
+    ADD COLUMN description_html TEXT;
+
+-- `body_html` is rendered when the comment is written, with mentions turned
+-- into links for the users they resolved to at that point.
+CREATE TABLE task_comments (
+    id SERIAL PRIMARY KEY,
+    task_id INT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    author_id INT REFERENCES users (id) ON DELETE SET NULL,
+    body TEXT NOT NULL,
+    body_html TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ
+);
+
+CREATE INDEX task_comments_task_id_idx ON task_comments (task_id, created_at);
+
+-- A mention in a task's description has no comment. Each user is recorded at
+-- most once per description or comment, so edits only notify new mentions.
+CREATE TABLE mentions (
+    id SERIAL PRIMARY KEY,
+    task_id INT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    comment_id INT REFERENCES task_comments (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    mentioned_by INT REFERENCES users (id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE UNIQUE INDEX mentions_description_user_idx
+    ON mentions (task_id, user_id) WHERE comment_id IS NULL;
+CREATE UNIQUE INDEX mentions_comment_user_idx
+    ON mentions (comment_id, user_id) WHERE comment_id IS NOT NULL;
+CREATE INDEX mentions_user_id_idx ON mentions (user_id, created_at DESC);
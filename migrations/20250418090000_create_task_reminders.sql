@@ -0,0 +1,22 @@
+-- Add migration script here
+ALTER TABLE tasks ADD COLUMN due_at TIMESTAMPTZ;
+
+ALTER TYPE notification_type ADD VALUE 'reminder';
+
+-- A reminder fires at `remind_at`, or `before_due_minutes` before the task's
+-- due date, so relative reminders follow the due date when it moves.
+-- `delivered_at` is set in the same transaction that locks the row for
+-- sending, which keeps each reminder from going out twice.
+CREATE TABLE task_reminders (
+    id SERIAL PRIMARY KEY,
+    task_id INT NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    remind_at TIMESTAMPTZ,
+    before_due_minutes INT CHECK (before_due_minutes >= 0),
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL,
+    CHECK (num_nonnulls(remind_at, before_due_minutes) = 1)
+);
+
+CREATE INDEX task_reminders_task_id_idx ON task_reminders (task_id);
+CREATE INDEX task_reminders_pending_idx ON task_reminders (remind_at) WHERE delivered_at IS NULL;
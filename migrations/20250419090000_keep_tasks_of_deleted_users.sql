@@ -0,0 +1,5 @@
+-- Add migration script here
+-- Tasks a deleted user created in a team workspace stay with the team.
+ALTER TABLE tasks DROP CONSTRAINT fk_user_id;
+
+ALTER TABLE tasks ADD CONSTRAINT fk_user_id FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL;
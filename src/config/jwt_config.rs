@@ -0,0 +1,62 @@
+use std::env;
+
+use jsonwebtoken::Algorithm;
+
+use super::var_or;
+
+#[derive(Debug)]
+pub struct JwtConfig {
+    /// Signing algorithm: HS256 (default), RS256 or EdDSA.
+    pub algorithm: Algorithm,
+    /// Shared secret for HS256. When set alongside an asymmetric algorithm it
+    /// is only used to verify tokens issued before the switch.
+    pub secret: Option<String>,
+    pub key_id: String,
+    pub private_key_path: Option<String>,
+    /// Verification keys as `(kid, public key PEM path)`, including the one
+    /// matching `private_key_path` and any still-valid rotated keys.
+    pub public_keys: Vec<(String, String)>,
+    pub issuer: String,
+    pub audience: String,
+    /// Clock skew tolerated when checking `exp` and `nbf`.
+    pub leeway_seconds: u64,
+    /// Accept tokens signed with `JWT_SECRET` before `iss`/`aud` were issued.
+    /// Off unless `JWT_ACCEPT_LEGACY_TOKENS` is set, as those tokens can't be
+    /// revoked.
+    pub accept_legacy_tokens: bool,
+}
+
+impl JwtConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let algorithm = match env::var("JWT_ALGORITHM").as_deref() {
+            Err(_) | Ok("HS256") => Algorithm::HS256,
+            Ok("RS256") => Algorithm::RS256,
+            Ok("EdDSA") => Algorithm::EdDSA,
+            Ok(other) => return Err(format!("Unsupported JWT_ALGORITHM: {}", other)),
+        };
+
+        let public_keys = env::var("JWT_PUBLIC_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                entry
+                    .split_once('=')
+                    .map(|(kid, path)| (kid.trim().to_string(), path.trim().to_string()))
+                    .ok_or(format!("JWT_PUBLIC_KEYS entry must be kid=path: {}", entry))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            algorithm,
+            secret: env::var("JWT_SECRET").ok(),
+            key_id: env::var("JWT_KEY_ID").unwrap_or_else(|_| "default".to_string()),
+            private_key_path: env::var("JWT_PRIVATE_KEY_PATH").ok(),
+            public_keys,
+            issuer: env::var("JWT_ISSUER").unwrap_or_else(|_| "todo_rs".to_string()),
+            audience: env::var("JWT_AUDIENCE").unwrap_or_else(|_| "todo_rs".to_string()),
+            leeway_seconds: var_or("JWT_LEEWAY_SECONDS", 60),
+            accept_legacy_tokens: var_or("JWT_ACCEPT_LEGACY_TOKENS", false),
+        })
+    }
+}
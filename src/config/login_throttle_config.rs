@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+use super::var_or;
+
+#[derive(Debug, Deserialize)]
+pub struct LoginThrottleConfig {
+    /// Failed logins for one account before it is locked.
+    pub max_failures: i32,
+    /// Failed logins from one IP address before it is locked.
+    pub ip_max_failures: i32,
+    pub lockout_minutes: i64,
+    pub backoff_base_seconds: i64,
+    pub backoff_max_seconds: i64,
+}
+
+impl LoginThrottleConfig {
+    pub fn from_env() -> Self {
+        Self {
+            max_failures: var_or("LOGIN_MAX_FAILURES", 5),
+            ip_max_failures: var_or("LOGIN_IP_MAX_FAILURES", 20),
+            lockout_minutes: var_or("LOGIN_LOCKOUT_MINUTES", 15),
+            backoff_base_seconds: var_or("LOGIN_BACKOFF_BASE_SECONDS", 1),
+            backoff_max_seconds: var_or("LOGIN_BACKOFF_MAX_SECONDS", 300),
+        }
+    }
+}
@@ -1,2 +1,18 @@
+use std::{env, str::FromStr};
+
 pub mod app_config;
+pub mod jwt_config;
+pub mod login_throttle_config;
+pub mod oidc_config;
+pub mod password_hash_config;
+pub mod password_policy_config;
 pub mod supabase_config;
+pub mod webauthn_config;
+
+/// Reads an optional setting, falling back to `default` when it is unset or invalid.
+pub fn var_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
@@ -0,0 +1,45 @@
+use std::env;
+
+#[derive(Debug, Clone)]
+pub struct OidcProviderConfig {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+}
+
+#[derive(Debug)]
+pub struct OidcConfig {
+    pub providers: Vec<OidcProviderConfig>,
+}
+
+impl OidcConfig {
+    /// Providers are listed in `OIDC_PROVIDERS` (e.g. `google,okta`) and each
+    /// one is configured with `OIDC_<NAME>_ISSUER`, `_CLIENT_ID`,
+    /// `_CLIENT_SECRET` (optional) and `_REDIRECT_URI`.
+    pub fn from_env() -> Result<Self, env::VarError> {
+        let providers = env::var("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let prefix = format!("OIDC_{}", name.to_uppercase());
+                Ok(OidcProviderConfig {
+                    name: name.to_lowercase(),
+                    issuer: env::var(format!("{}_ISSUER", prefix))?,
+                    client_id: env::var(format!("{}_CLIENT_ID", prefix))?,
+                    client_secret: env::var(format!("{}_CLIENT_SECRET", prefix)).ok(),
+                    redirect_uri: env::var(format!("{}_REDIRECT_URI", prefix))?,
+                })
+            })
+            .collect::<Result<Vec<_>, env::VarError>>()?;
+
+        Ok(Self { providers })
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&OidcProviderConfig> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+}
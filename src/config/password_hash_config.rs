@@ -0,0 +1,46 @@
+use std::{env, fs};
+
+use argon2::Params;
+
+use super::var_or;
+
+#[derive(Debug)]
+pub struct PasswordHashConfig {
+    /// Argon2id memory cost in KiB.
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// Secret mixed into every hash and kept out of the database, from
+    /// `PASSWORD_PEPPER` or the file named by `PASSWORD_PEPPER_FILE`.
+    pub pepper: Option<String>,
+}
+
+impl PasswordHashConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let pepper = match env::var("PASSWORD_PEPPER_FILE") {
+            Ok(path) => Some(
+                fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path, e))?
+                    .trim()
+                    .to_string(),
+            ),
+            Err(_) => env::var("PASSWORD_PEPPER").ok(),
+        }
+        .filter(|pepper| !pepper.is_empty());
+
+        let config = Self {
+            memory_kib: var_or("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST),
+            iterations: var_or("ARGON2_ITERATIONS", Params::DEFAULT_T_COST),
+            parallelism: var_or("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+            pepper,
+        };
+        Params::new(
+            config.memory_kib,
+            config.iterations,
+            config.parallelism,
+            None,
+        )
+        .map_err(|e| format!("Invalid Argon2 parameters: {}", e))?;
+        Ok(config)
+    }
+}
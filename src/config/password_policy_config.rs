@@ -0,0 +1,37 @@
+use std::env;
+
+use serde::Deserialize;
+
+use super::var_or;
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordPolicyConfig {
+    /// Minimum zxcvbn score, 0 to 4.
+    pub min_score: u8,
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Breached-password list in Have I Been Pwned format: either a directory
+    /// of range files named by SHA-1 prefix (`ABCDE.txt`, lines `SUFFIX:COUNT`)
+    /// or a single file of `HASH:COUNT` lines. Disabled when unset.
+    pub breached_passwords_path: Option<String>,
+}
+
+impl PasswordPolicyConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let config = Self {
+            min_score: var_or::<u8>("PASSWORD_MIN_SCORE", 3).min(4),
+            min_length: var_or("PASSWORD_MIN_LENGTH", 8),
+            max_length: var_or("PASSWORD_MAX_LENGTH", 128),
+            breached_passwords_path: env::var("PASSWORD_BREACHED_LIST_PATH")
+                .ok()
+                .filter(|path| !path.is_empty()),
+        };
+        if config.min_length > config.max_length {
+            return Err(format!(
+                "PASSWORD_MIN_LENGTH ({}) is greater than PASSWORD_MAX_LENGTH ({})",
+                config.min_length, config.max_length
+            ));
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,21 @@
+use std::env;
+
+#[derive(Debug)]
+pub struct WebauthnConfig {
+    /// Relying party ID, the registrable domain passkeys are scoped to.
+    pub rp_id: String,
+    pub rp_name: String,
+    /// Origin the browser reports in `clientDataJSON`, e.g. `https://app.example.com`.
+    pub origin: String,
+}
+
+impl WebauthnConfig {
+    pub fn from_env() -> Self {
+        Self {
+            rp_id: env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".to_string()),
+            rp_name: env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "Todo.rs".to_string()),
+            origin: env::var("WEBAUTHN_ORIGIN")
+                .unwrap_or_else(|_| "http://localhost:3000".to_string()),
+        }
+    }
+}
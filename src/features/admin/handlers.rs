@@ -0,0 +1,109 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::{
+    models::{AuthResponse, Claims, ClientInfo},
+    scopes::{RequireScope, UsersRead, UsersWrite},
+};
+
+use super::{
+    models::{AdminError, AdminUser, AuditLogEntry, AuditLogQueryDto, UserSearchDto},
+    services::AdminService,
+};
+
+pub async fn get_users(
+    State(pool): State<PgPool>,
+    _scope: RequireScope<UsersRead>,
+    Query(user_search_dto): Query<UserSearchDto>,
+) -> Result<Json<Vec<AdminUser>>, AdminError> {
+    let users = AdminService::search_users(&pool, user_search_dto).await?;
+    Ok(Json(users))
+}
+
+pub async fn get_user(
+    State(pool): State<PgPool>,
+    _scope: RequireScope<UsersRead>,
+    Path(id): Path<i32>,
+) -> Result<Json<AdminUser>, AdminError> {
+    let user = AdminService::get_user(&pool, id).await?;
+    Ok(Json(user))
+}
+
+pub async fn verify_user(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    _scope: RequireScope<UsersWrite>,
+    Path(id): Path<i32>,
+) -> Result<Json<AdminUser>, AdminError> {
+    let user = AdminService::verify_user(&pool, &claims, &client, id).await?;
+    Ok(Json(user))
+}
+
+pub async fn lock_user(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    _scope: RequireScope<UsersWrite>,
+    Path(id): Path<i32>,
+) -> Result<Json<AdminUser>, AdminError> {
+    let user = AdminService::lock_user(&pool, &claims, &client, id).await?;
+    Ok(Json(user))
+}
+
+pub async fn unlock_user(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    _scope: RequireScope<UsersWrite>,
+    Path(id): Path<i32>,
+) -> Result<Json<AdminUser>, AdminError> {
+    let user = AdminService::unlock_user(&pool, &claims, &client, id).await?;
+    Ok(Json(user))
+}
+
+pub async fn delete_user(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    _scope: RequireScope<UsersWrite>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AdminError> {
+    AdminService::delete_user(&pool, &claims, &client, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn force_password_reset(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    _scope: RequireScope<UsersWrite>,
+    Path(id): Path<i32>,
+) -> Result<Json<AdminUser>, AdminError> {
+    let user = AdminService::force_password_reset(&pool, &claims, &client, id).await?;
+    Ok(Json(user))
+}
+
+pub async fn impersonate_user(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    _scope: RequireScope<UsersWrite>,
+    Path(id): Path<i32>,
+) -> Result<Json<AuthResponse>, AdminError> {
+    let auth_response = AdminService::impersonate(&pool, &claims, &client, id).await?;
+    Ok(Json(auth_response))
+}
+
+pub async fn get_audit_log(
+    State(pool): State<PgPool>,
+    _scope: RequireScope<UsersRead>,
+    Query(audit_log_query_dto): Query<AuditLogQueryDto>,
+) -> Result<Json<Vec<AuditLogEntry>>, AdminError> {
+    let entries = AdminService::get_audit_log(&pool, audit_log_query_dto).await?;
+    Ok(Json(entries))
+}
@@ -0,0 +1,80 @@
+use axum::{
+    body::Body,
+    http::{Response, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::features::auth::models::Role;
+
+/// A user as operators see it: account state, never credentials.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct AdminUser {
+    pub id: i32,
+    pub email: String,
+    pub display_name: Option<String>,
+    pub role: Role,
+    pub verified: bool,
+    pub totp_enabled: bool,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub password_reset_required: bool,
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserSearchDto {
+    /// Matched against email and display name.
+    pub search: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogQueryDto {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "admin_action", rename_all = "snake_case")]
+pub enum AdminAction {
+    Verify,
+    Lock,
+    Unlock,
+    Delete,
+    ForcePasswordReset,
+    Impersonate,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AuditLogEntry {
+    pub id: i32,
+    pub admin_id: Option<i32>,
+    pub target_user_id: Option<i32>,
+    pub target_email: String,
+    pub action: AdminAction,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub enum AdminError {
+    NotFound,
+    /// Admins can't lock, delete or impersonate themselves or other admins.
+    Forbidden,
+    InternalServerError,
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            AdminError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            AdminError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            AdminError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
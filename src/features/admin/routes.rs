@@ -0,0 +1,38 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::middlewares::{admin_middleware, jwt_middleware};
+
+use super::handlers;
+
+pub fn admin_routes(pool: PgPool) -> Router {
+    Router::new()
+        .route("/admin/users", get(handlers::get_users))
+        .route(
+            "/admin/users/{id}",
+            get(handlers::get_user).delete(handlers::delete_user),
+        )
+        .route("/admin/users/{id}/verify", post(handlers::verify_user))
+        .route("/admin/users/{id}/lock", post(handlers::lock_user))
+        .route("/admin/users/{id}/unlock", post(handlers::unlock_user))
+        .route(
+            "/admin/users/{id}/password-reset",
+            post(handlers::force_password_reset),
+        )
+        .route(
+            "/admin/users/{id}/impersonate",
+            post(handlers::impersonate_user),
+        )
+        .route("/admin/audit-log", get(handlers::get_audit_log))
+        // The last layer runs first, so the token is validated before the role is checked.
+        .layer(middleware::from_fn_with_state(
+            pool.clone(),
+            admin_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
+        .with_state(pool)
+}
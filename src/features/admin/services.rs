@@ -0,0 +1,318 @@
+use sqlx::{PgConnection, PgPool};
+
+use crate::features::auth::{
+    models::{AuthResponse, Claims, ClientInfo, Role},
+    services::AuthService,
+};
+
+use super::models::{
+    AdminAction, AdminError, AdminUser, AuditLogEntry, AuditLogQueryDto, UserSearchDto,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+const IMPERSONATION_TTL_MINUTES: i64 = 60;
+
+pub struct AdminService;
+
+impl AdminService {
+    pub async fn search_users(
+        pool: &PgPool,
+        user_search_dto: UserSearchDto,
+    ) -> Result<Vec<AdminUser>, AdminError> {
+        let search = user_search_dto.search.map(|search| format!("%{}%", search));
+        let (limit, offset) = Self::page(user_search_dto.limit, user_search_dto.offset);
+
+        sqlx::query_as(
+            r#"
+            SELECT id, email, display_name, role, COALESCE(verified, FALSE) AS verified,
+                   totp_enabled, locked_at, password_reset_required, deletion_scheduled_at,
+                   created_at
+            FROM users
+            WHERE $1::TEXT IS NULL OR email ILIKE $1 OR display_name ILIKE $1
+            ORDER BY id
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(search)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AdminError::InternalServerError)
+    }
+
+    pub async fn get_user(pool: &PgPool, user_id: i32) -> Result<AdminUser, AdminError> {
+        sqlx::query_as(
+            r#"
+            SELECT id, email, display_name, role, COALESCE(verified, FALSE) AS verified,
+                   totp_enabled, locked_at, password_reset_required, deletion_scheduled_at,
+                   created_at
+            FROM users
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AdminError::InternalServerError)?
+        .ok_or(AdminError::NotFound)
+    }
+
+    pub async fn verify_user(
+        pool: &PgPool,
+        admin: &Claims,
+        client: &ClientInfo,
+        user_id: i32,
+    ) -> Result<AdminUser, AdminError> {
+        let user = Self::get_user(pool, user_id).await?;
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        sqlx::query("UPDATE users SET verified = TRUE, verification_token = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        Self::record(&mut tx, admin, client, &user, AdminAction::Verify).await?;
+        tx.commit()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+
+        Self::get_user(pool, user_id).await
+    }
+
+    /// Locks the account and signs it out everywhere; personal access tokens
+    /// stop working while it stays locked.
+    pub async fn lock_user(
+        pool: &PgPool,
+        admin: &Claims,
+        client: &ClientInfo,
+        user_id: i32,
+    ) -> Result<AdminUser, AdminError> {
+        let user = Self::get_user(pool, user_id).await?;
+        Self::ensure_manageable(admin, &user)?;
+
+        let now = chrono::Utc::now();
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        sqlx::query("UPDATE users SET locked_at = COALESCE(locked_at, $2) WHERE id = $1")
+            .bind(user_id)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        Self::revoke_sessions(&mut tx, user_id).await?;
+        Self::record(&mut tx, admin, client, &user, AdminAction::Lock).await?;
+        tx.commit()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+
+        Self::get_user(pool, user_id).await
+    }
+
+    pub async fn unlock_user(
+        pool: &PgPool,
+        admin: &Claims,
+        client: &ClientInfo,
+        user_id: i32,
+    ) -> Result<AdminUser, AdminError> {
+        let user = Self::get_user(pool, user_id).await?;
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        sqlx::query("UPDATE users SET locked_at = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        Self::record(&mut tx, admin, client, &user, AdminAction::Unlock).await?;
+        tx.commit()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+
+        Self::get_user(pool, user_id).await
+    }
+
+    /// Deletes right away, without the grace period users get.
+    pub async fn delete_user(
+        pool: &PgPool,
+        admin: &Claims,
+        client: &ClientInfo,
+        user_id: i32,
+    ) -> Result<(), AdminError> {
+        let user = Self::get_user(pool, user_id).await?;
+        Self::ensure_manageable(admin, &user)?;
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        // Recorded first: the entry's `target_user_id` is nulled by the delete.
+        Self::record(&mut tx, admin, client, &user, AdminAction::Delete).await?;
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        tx.commit()
+            .await
+            .map_err(|_| AdminError::InternalServerError)
+    }
+
+    /// Signs the user out and blocks password sign-in until they set a new
+    /// password through the emailed link.
+    pub async fn force_password_reset(
+        pool: &PgPool,
+        admin: &Claims,
+        client: &ClientInfo,
+        user_id: i32,
+    ) -> Result<AdminUser, AdminError> {
+        let user = Self::get_user(pool, user_id).await?;
+        Self::ensure_manageable(admin, &user)?;
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        sqlx::query("UPDATE users SET password_reset_required = TRUE WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        Self::revoke_sessions(&mut tx, user_id).await?;
+        Self::record(
+            &mut tx,
+            admin,
+            client,
+            &user,
+            AdminAction::ForcePasswordReset,
+        )
+        .await?;
+        tx.commit()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+
+        AuthService::send_password_reset_link(pool, user.id, &user.email)
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to send password reset link: {}", err);
+                AdminError::InternalServerError
+            })?;
+
+        Self::get_user(pool, user_id).await
+    }
+
+    /// Issues a short-lived token for the user with the admin recorded in its
+    /// `act` claim. The audit entry is written before the token exists.
+    pub async fn impersonate(
+        pool: &PgPool,
+        admin: &Claims,
+        client: &ClientInfo,
+        user_id: i32,
+    ) -> Result<AuthResponse, AdminError> {
+        let target = Self::get_user(pool, user_id).await?;
+        Self::ensure_manageable(admin, &target)?;
+
+        let mut conn = pool
+            .acquire()
+            .await
+            .map_err(|_| AdminError::InternalServerError)?;
+        Self::record(&mut conn, admin, client, &target, AdminAction::Impersonate).await?;
+
+        let user = AuthService::find_user_by_id(pool, user_id)
+            .await
+            .map_err(|_| AdminError::NotFound)?;
+        AuthService::create_impersonation_response(
+            pool,
+            &user,
+            admin.sub,
+            client,
+            chrono::Duration::minutes(IMPERSONATION_TTL_MINUTES),
+        )
+        .await
+        .map_err(|_| AdminError::InternalServerError)
+    }
+
+    pub async fn get_audit_log(
+        pool: &PgPool,
+        audit_log_query_dto: AuditLogQueryDto,
+    ) -> Result<Vec<AuditLogEntry>, AdminError> {
+        let (limit, offset) = Self::page(audit_log_query_dto.limit, audit_log_query_dto.offset);
+
+        sqlx::query_as(
+            r#"
+            SELECT id, admin_id, target_user_id, target_email, action, ip_address, user_agent,
+                   created_at
+            FROM admin_audit_log
+            ORDER BY created_at DESC, id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AdminError::InternalServerError)
+    }
+
+    fn ensure_manageable(admin: &Claims, target: &AdminUser) -> Result<(), AdminError> {
+        if target.id == admin.sub || target.role == Role::Admin {
+            return Err(AdminError::Forbidden);
+        }
+        Ok(())
+    }
+
+    fn page(limit: Option<i64>, offset: Option<i64>) -> (i64, i64) {
+        (
+            limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+            offset.unwrap_or(0).max(0),
+        )
+    }
+
+    async fn revoke_sessions(conn: &mut PgConnection, user_id: i32) -> Result<(), AdminError> {
+        sqlx::query(
+            "UPDATE sessions SET revoked_at = $2 WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(chrono::Utc::now())
+        .execute(conn)
+        .await
+        .map_err(|_| AdminError::InternalServerError)?;
+        Ok(())
+    }
+
+    async fn record(
+        conn: &mut PgConnection,
+        admin: &Claims,
+        client: &ClientInfo,
+        target: &AdminUser,
+        action: AdminAction,
+    ) -> Result<(), AdminError> {
+        sqlx::query(
+            r#"
+            INSERT INTO admin_audit_log
+                (admin_id, target_user_id, target_email, action, ip_address, user_agent,
+                 created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(admin.sub)
+        .bind(target.id)
+        .bind(&target.email)
+        .bind(action)
+        .bind(&client.ip)
+        .bind(&client.user_agent)
+        .bind(chrono::Utc::now())
+        .execute(conn)
+        .await
+        .map_err(|_| AdminError::InternalServerError)?;
+        Ok(())
+    }
+}
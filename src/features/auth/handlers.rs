@@ -1,53 +1,268 @@
 use super::{
-    models::{AuthCredentialsDto, AuthResponse},
+    keys::KEYS,
+    models::{
+        AuthCredentialsDto, AuthError, AuthResponse, Claims, ClientInfo, LoginResponse,
+        MagicLinkRequestDto, MagicLinkVerifyDto, MfaLoginDto, MfaPasskeyDto, MfaTokenDto,
+        OidcCallbackDto, Passkey, PasskeyAssertionDto, PublicKeyCredentialCreationOptions,
+        PublicKeyCredentialRequestOptions, RecoveryCodesResponse, RegisterPasskeyDto,
+        ResetPasswordDto, TotpCodeDto, TotpEnrollmentResponse,
+    },
+    oidc::OidcService,
+    password_policy::PasswordPolicy,
+    scopes::{RequireScope, RequireSession, UsersWrite},
     services::AuthService,
+    throttle::LoginThrottle,
+    webauthn::WebauthnService,
 };
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Extension, Json,
+};
+use jsonwebtoken::jwk::JwkSet;
 use sqlx::PgPool;
+use validator::Validate;
 
 pub async fn login(
     State(pool): State<PgPool>,
+    client: ClientInfo,
     Json(auth_credentials_dto): Json<AuthCredentialsDto>,
-) -> Result<Json<AuthResponse>, (StatusCode, String)> {
+) -> Result<Json<LoginResponse>, AuthError> {
     let AuthCredentialsDto { email, password } = auth_credentials_dto;
-    match AuthService::find_user(&pool, &email, &password).await {
-        Ok(user) => {
-            let auth_response = AuthService::create_auth_response(&user).unwrap();
-            Ok(Json(auth_response))
+    let ip = client.ip.clone().unwrap_or_default();
+
+    LoginThrottle::check(&pool, &email, &ip).await?;
+
+    let user = match AuthService::find_user(&pool, &email, &password).await {
+        Ok(user) => user,
+        Err(AuthError::WrongCredentials) => {
+            LoginThrottle::record_failure(&pool, &email, &ip).await?;
+            return Err(AuthError::WrongCredentials);
         }
-        Err(_) => Err((StatusCode::UNAUTHORIZED, "Wrong credentials".to_string())),
+        Err(err) => return Err(err),
+    };
+    LoginThrottle::record_success(&pool, &email).await?;
+
+    let login_response = AuthService::complete_login(&pool, &user, &client).await?;
+    Ok(Json(login_response))
+}
+
+pub async fn request_magic_link(
+    State(pool): State<PgPool>,
+    Json(magic_link_request_dto): Json<MagicLinkRequestDto>,
+) -> Result<Json<String>, (StatusCode, String)> {
+    if let Err(errors) = magic_link_request_dto.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Validation error: {:?}", errors),
+        ));
     }
+
+    if let Err(err) = AuthService::send_magic_link(&pool, &magic_link_request_dto.email).await {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, err));
+    }
+    Ok(Json(
+        "If the email belongs to an account, a sign-in link is on its way".to_string(),
+    ))
+}
+
+pub async fn verify_magic_link(
+    State(pool): State<PgPool>,
+    client: ClientInfo,
+    Json(magic_link_verify_dto): Json<MagicLinkVerifyDto>,
+) -> Result<Json<LoginResponse>, AuthError> {
+    let user = AuthService::consume_magic_link(&pool, &magic_link_verify_dto.token).await?;
+    let login_response = AuthService::complete_login(&pool, &user, &client).await?;
+    Ok(Json(login_response))
+}
+
+pub async fn oidc_login(
+    State(pool): State<PgPool>,
+    Path(provider): Path<String>,
+) -> Result<Redirect, AuthError> {
+    let authorization_url = OidcService::authorization_url(&pool, &provider).await?;
+    Ok(Redirect::to(&authorization_url))
+}
+
+pub async fn oidc_callback(
+    State(pool): State<PgPool>,
+    client: ClientInfo,
+    Path(provider): Path<String>,
+    Query(oidc_callback_dto): Query<OidcCallbackDto>,
+) -> Result<Json<LoginResponse>, AuthError> {
+    let OidcCallbackDto { code, state, error } = oidc_callback_dto;
+    if let Some(error) = error {
+        tracing::warn!("OIDC provider {} returned error: {}", provider, error);
+        return Err(AuthError::InvalidOidcResponse);
+    }
+    let code = code.ok_or(AuthError::InvalidOidcResponse)?;
+
+    let id_token_claims = OidcService::complete_login(&pool, &provider, &code, &state).await?;
+    let user = AuthService::find_or_link_oidc_user(&pool, &provider, &id_token_claims).await?;
+
+    let login_response = AuthService::complete_login(&pool, &user, &client).await?;
+    Ok(Json(login_response))
+}
+
+pub async fn login_mfa(
+    State(pool): State<PgPool>,
+    client: ClientInfo,
+    Json(mfa_login_dto): Json<MfaLoginDto>,
+) -> Result<Json<AuthResponse>, AuthError> {
+    let MfaLoginDto { mfa_token, code } = mfa_login_dto;
+    let mfa_claims = AuthService::validate_mfa_token(&mfa_token)?;
+    let ip = client.ip.clone().unwrap_or_default();
+
+    LoginThrottle::check_second_factor(&pool, mfa_claims.sub, &ip).await?;
+
+    let user = AuthService::find_user_by_id(&pool, mfa_claims.sub)
+        .await
+        .map_err(|_| AuthError::WrongCredentials)?;
+    match AuthService::verify_second_factor(&pool, user.id, &code).await {
+        Ok(()) => LoginThrottle::record_second_factor_success(&pool, user.id).await?,
+        Err(AuthError::InvalidMfaCode) => {
+            LoginThrottle::record_second_factor_failure(&pool, user.id, &user.email, &ip).await?;
+            return Err(AuthError::InvalidMfaCode);
+        }
+        Err(err) => return Err(err),
+    }
+    let auth_response = AuthService::create_auth_response(&pool, &user, &client).await?;
+    Ok(Json(auth_response))
+}
+
+pub async fn passkey_login_options(
+    State(pool): State<PgPool>,
+) -> Result<Json<PublicKeyCredentialRequestOptions>, AuthError> {
+    let options = WebauthnService::authentication_options(&pool, None, true).await?;
+    Ok(Json(options))
+}
+
+/// A passkey with user verification is both factors, so it skips the MFA step.
+pub async fn passkey_login(
+    State(pool): State<PgPool>,
+    client: ClientInfo,
+    Json(passkey_assertion_dto): Json<PasskeyAssertionDto>,
+) -> Result<Json<AuthResponse>, AuthError> {
+    let user_id = WebauthnService::authenticate(&pool, &passkey_assertion_dto, None, true).await?;
+
+    let user = AuthService::find_user_by_id(&pool, user_id)
+        .await
+        .map_err(|_| AuthError::WrongCredentials)?;
+    let auth_response = AuthService::create_auth_response(&pool, &user, &client).await?;
+    Ok(Json(auth_response))
+}
+
+pub async fn mfa_passkey_options(
+    State(pool): State<PgPool>,
+    Json(mfa_token_dto): Json<MfaTokenDto>,
+) -> Result<Json<PublicKeyCredentialRequestOptions>, AuthError> {
+    let mfa_claims = AuthService::validate_mfa_token(&mfa_token_dto.mfa_token)?;
+    let options =
+        WebauthnService::authentication_options(&pool, Some(mfa_claims.sub), false).await?;
+    Ok(Json(options))
+}
+
+pub async fn login_mfa_passkey(
+    State(pool): State<PgPool>,
+    client: ClientInfo,
+    Json(mfa_passkey_dto): Json<MfaPasskeyDto>,
+) -> Result<Json<AuthResponse>, AuthError> {
+    let MfaPasskeyDto {
+        mfa_token,
+        credential,
+    } = mfa_passkey_dto;
+    let mfa_claims = AuthService::validate_mfa_token(&mfa_token)?;
+
+    WebauthnService::authenticate(&pool, &credential, Some(mfa_claims.sub), false).await?;
+
+    let user = AuthService::find_user_by_id(&pool, mfa_claims.sub)
+        .await
+        .map_err(|_| AuthError::WrongCredentials)?;
+    let auth_response = AuthService::create_auth_response(&pool, &user, &client).await?;
+    Ok(Json(auth_response))
+}
+
+pub async fn passkey_registration_options(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    _session: RequireSession,
+) -> Result<Json<PublicKeyCredentialCreationOptions>, AuthError> {
+    let user = AuthService::find_user_by_id(&pool, claims.sub)
+        .await
+        .map_err(|_| AuthError::InvalidToken)?;
+    let options = WebauthnService::registration_options(&pool, user.id, &user.email).await?;
+    Ok(Json(options))
+}
+
+pub async fn register_passkey(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    _session: RequireSession,
+    Json(register_passkey_dto): Json<RegisterPasskeyDto>,
+) -> Result<(StatusCode, Json<Passkey>), AuthError> {
+    let passkey = WebauthnService::register(&pool, claims.sub, register_passkey_dto).await?;
+    Ok((StatusCode::CREATED, Json(passkey)))
+}
+
+pub async fn enroll_totp(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    _session: RequireSession,
+) -> Result<Json<TotpEnrollmentResponse>, AuthError> {
+    let enrollment = AuthService::start_totp_enrollment(&pool, claims.sub).await?;
+    Ok(Json(enrollment))
+}
+
+pub async fn confirm_totp(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    _session: RequireSession,
+    Json(totp_code_dto): Json<TotpCodeDto>,
+) -> Result<Json<RecoveryCodesResponse>, AuthError> {
+    let recovery_codes =
+        AuthService::confirm_totp_enrollment(&pool, claims.sub, totp_code_dto.code.trim()).await?;
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
 }
 
 pub async fn register(
     State(pool): State<PgPool>,
     Json(auth_credentials_dto): Json<AuthCredentialsDto>,
-) -> Result<Json<String>, (StatusCode, String)> {
+) -> Result<Json<String>, Response> {
     if let Err(errors) = AuthService::validate_credentials(&auth_credentials_dto) {
         return Err((
             StatusCode::BAD_REQUEST,
             format!("Validation error: {:?}", errors),
-        ));
+        )
+            .into_response());
     }
 
     let AuthCredentialsDto { email, password } = auth_credentials_dto;
 
-    if let Err(message) = AuthService::check_password_strength(&password) {
-        return Err((StatusCode::BAD_REQUEST, message));
-    }
+    PasswordPolicy::check(&password, &AuthService::password_user_inputs(&email))
+        .await
+        .map_err(IntoResponse::into_response)?;
 
+    // An existing email gets the same response as a new one, so registration
+    // can't be used to discover accounts; the owner is told by email instead.
     if AuthService::email_exists(&pool, &email)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())?
     {
-        return Err((StatusCode::CONFLICT, "Email already exists".to_string()));
-    }
-
-    if let Err(err) = AuthService::create_user(&pool, &email, &password).await {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string()));
+        // Hash anyway so both branches take about as long.
+        let _ = AuthService::hash_password(&password).await;
+        if let Err(err) = AuthService::send_already_registered_email(&email).await {
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, err).into_response());
+        }
+    } else if let Err(err) = AuthService::create_user(&pool, &email, &password).await {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response());
     }
 
-    Ok(Json(format!("User registered successfully")))
+    Ok(Json("User registered successfully".to_string()))
 }
 
 pub async fn verify_email(
@@ -59,3 +274,16 @@ pub async fn verify_email(
     }
     Ok(Json("Email verified successfully".to_string()))
 }
+
+pub async fn reset_password(
+    State(pool): State<PgPool>,
+    Json(reset_password_dto): Json<ResetPasswordDto>,
+) -> Result<Json<String>, AuthError> {
+    let ResetPasswordDto { token, password } = reset_password_dto;
+    AuthService::reset_password(&pool, &token, &password).await?;
+    Ok(Json("Password has been reset".to_string()))
+}
+
+pub async fn jwks() -> Json<JwkSet> {
+    Json(KEYS.jwks.clone())
+}
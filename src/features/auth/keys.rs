@@ -0,0 +1,229 @@
+use std::{collections::HashMap, fs, sync::LazyLock};
+
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::{Error, ErrorKind},
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rsa::{
+    pkcs1::DecodeRsaPublicKey, pkcs8::DecodePublicKey, traits::PublicKeyParts, RsaPublicKey,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config::jwt_config::JwtConfig;
+
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo; the raw key is the 32 bytes after it.
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+pub static KEYS: LazyLock<Keys> = LazyLock::new(|| {
+    let config = JwtConfig::from_env().expect("Failed to load JWT config");
+    Keys::from_config(&config).expect("Failed to load JWT keys")
+});
+
+pub struct VerificationKey {
+    pub algorithm: Algorithm,
+    pub decoding: DecodingKey,
+}
+
+pub struct Keys {
+    pub algorithm: Algorithm,
+    /// `kid` written into the header of every token we sign; `None` for HS256.
+    pub kid: Option<String>,
+    pub encoding: EncodingKey,
+    /// Asymmetric verification keys by `kid`.
+    pub verification: HashMap<String, VerificationKey>,
+    /// HS256 secret, used for tokens that carry no `kid`.
+    pub legacy: Option<VerificationKey>,
+    pub jwks: JwkSet,
+    pub issuer: String,
+    pub audience: String,
+    pub leeway_seconds: u64,
+    pub accept_legacy_tokens: bool,
+}
+
+impl Keys {
+    pub fn from_config(config: &JwtConfig) -> Result<Self, String> {
+        let legacy = config.secret.as_ref().map(|secret| VerificationKey {
+            algorithm: Algorithm::HS256,
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+        });
+
+        if config.algorithm == Algorithm::HS256 {
+            let secret = config.secret.as_ref().ok_or("JWT_SECRET must be set")?;
+            return Ok(Self {
+                algorithm: Algorithm::HS256,
+                kid: None,
+                encoding: EncodingKey::from_secret(secret.as_bytes()),
+                verification: HashMap::new(),
+                legacy,
+                jwks: JwkSet { keys: Vec::new() },
+                issuer: config.issuer.clone(),
+                audience: config.audience.clone(),
+                leeway_seconds: config.leeway_seconds,
+                accept_legacy_tokens: config.accept_legacy_tokens,
+            });
+        }
+
+        let private_key_path = config
+            .private_key_path
+            .as_ref()
+            .ok_or("JWT_PRIVATE_KEY_PATH must be set")?;
+        let private_pem = fs::read(private_key_path)
+            .map_err(|e| format!("Failed to read {}: {}", private_key_path, e))?;
+        let encoding = match config.algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_pem),
+            _ => EncodingKey::from_ed_pem(&private_pem),
+        }
+        .map_err(|e| format!("Invalid private key: {}", e))?;
+
+        let mut verification = HashMap::new();
+        let mut jwks = Vec::new();
+        for (kid, path) in &config.public_keys {
+            let pem = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let (key, jwk) = Self::load_public_key(kid, &pem)?;
+            verification.insert(kid.clone(), key);
+            jwks.push(jwk);
+        }
+
+        match verification.get(&config.key_id) {
+            Some(key) if key.algorithm == config.algorithm => {}
+            _ => {
+                return Err(format!(
+                    "JWT_PUBLIC_KEYS has no {:?} key for JWT_KEY_ID {}",
+                    config.algorithm, config.key_id
+                ))
+            }
+        }
+
+        Ok(Self {
+            algorithm: config.algorithm,
+            kid: Some(config.key_id.clone()),
+            encoding,
+            verification,
+            legacy,
+            jwks: JwkSet { keys: jwks },
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            leeway_seconds: config.leeway_seconds,
+            accept_legacy_tokens: config.accept_legacy_tokens,
+        })
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, Error> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.kid.clone();
+        encode(&header, claims, &self.encoding)
+    }
+
+    /// Verifies the signature, `exp`, `nbf`, `iss` and `aud`. Tokens without
+    /// a `kid`, `iss` and `aud` are the pre-rotation HS256 tokens and are only
+    /// checked for `exp` while `accept_legacy_tokens` is set.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, Error> {
+        let header = decode_header(token)?;
+        let is_legacy = header.kid.is_none();
+        let key = match header.kid {
+            Some(kid) => self.verification.get(&kid),
+            None => self.legacy.as_ref(),
+        }
+        .ok_or(ErrorKind::InvalidToken)?;
+
+        match decode::<T>(token, &key.decoding, &self.validation(key.algorithm)) {
+            Err(err)
+                if is_legacy
+                    && self.accept_legacy_tokens
+                    && matches!(err.kind(), ErrorKind::MissingRequiredClaim(_)) =>
+            {
+                decode::<T>(token, &key.decoding, &self.legacy_validation()).map(|data| data.claims)
+            }
+            result => result.map(|data| data.claims),
+        }
+    }
+
+    fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "sub", "iss", "aud"]);
+        validation.validate_nbf = true;
+        validation.leeway = self.leeway_seconds;
+        validation
+    }
+
+    fn legacy_validation(&self) -> Validation {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = self.leeway_seconds;
+        validation
+    }
+
+    /// Parses an RSA (PKCS#1 or SPKI) or Ed25519 (SPKI) public key PEM.
+    fn load_public_key(kid: &str, pem: &[u8]) -> Result<(VerificationKey, Jwk), String> {
+        let pem_str = std::str::from_utf8(pem).map_err(|e| e.to_string())?;
+        let rsa_key = RsaPublicKey::from_public_key_pem(pem_str)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem_str));
+
+        if let Ok(rsa_key) = rsa_key {
+            let n = BASE64URL_NOPAD.encode(&rsa_key.n().to_bytes_be());
+            let e = BASE64URL_NOPAD.encode(&rsa_key.e().to_bytes_be());
+            let decoding = DecodingKey::from_rsa_components(&n, &e)
+                .map_err(|e| format!("Invalid RSA key {}: {}", kid, e))?;
+            let jwk = Self::jwk(
+                kid,
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n,
+                    e,
+                }),
+            );
+            let key = VerificationKey {
+                algorithm: Algorithm::RS256,
+                decoding,
+            };
+            return Ok((key, jwk));
+        }
+
+        let der = pem::parse(pem)
+            .map_err(|e| format!("Invalid PEM for key {}: {}", kid, e))?
+            .into_contents();
+        let raw = der
+            .strip_prefix(&ED25519_SPKI_PREFIX)
+            .filter(|raw| raw.len() == 32)
+            .ok_or(format!("Key {} is neither RSA nor Ed25519", kid))?;
+        let x = BASE64URL_NOPAD.encode(raw);
+        let decoding = DecodingKey::from_ed_components(&x)
+            .map_err(|e| format!("Invalid Ed25519 key {}: {}", kid, e))?;
+        let jwk = Self::jwk(
+            kid,
+            KeyAlgorithm::EdDSA,
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x,
+            }),
+        );
+        let key = VerificationKey {
+            algorithm: Algorithm::EdDSA,
+            decoding,
+        };
+        Ok((key, jwk))
+    }
+
+    fn jwk(kid: &str, key_algorithm: KeyAlgorithm, algorithm: AlgorithmParameters) -> Jwk {
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm,
+        }
+    }
+}
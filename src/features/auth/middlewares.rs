@@ -1,13 +1,21 @@
 use axum::{
     body::Body,
+    extract::State,
     http::{Request, StatusCode},
     middleware::Next,
     response::Response,
 };
+use sqlx::PgPool;
 
-use super::services::AuthService;
+use super::{
+    models::{AuthError, Claims, Role},
+    services::AuthService,
+};
 
+/// Accepts either a JWT or a personal access token and stores the resulting
+/// `Claims` in the request extensions.
 pub async fn jwt_middleware(
+    State(pool): State<PgPool>,
     mut request: Request<Body>,
     next: Next,
 ) -> Result<Response, StatusCode> {
@@ -18,7 +26,18 @@ pub async fn jwt_middleware(
         .and_then(|header| header.strip_prefix("Bearer "))
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    match AuthService::validate_token(token) {
+    let claims = if AuthService::is_personal_access_token(token) {
+        AuthService::validate_personal_access_token(&pool, token).await
+    } else {
+        match AuthService::validate_token(token) {
+            Ok(claims) => AuthService::validate_session(&pool, &claims)
+                .await
+                .map(|_| claims),
+            Err(err) => Err(err),
+        }
+    };
+
+    match claims {
         Ok(claims) => {
             request.extensions_mut().insert(claims);
             Ok(next.run(request).await)
@@ -26,3 +45,29 @@ pub async fn jwt_middleware(
         Err(_) => Err(StatusCode::UNAUTHORIZED),
     }
 }
+
+/// Layered inside `jwt_middleware`; lets only admins through. The role is
+/// read from the database rather than the token, so a demoted or locked
+/// admin loses access at once, and impersonation tokens never get in.
+pub async fn admin_middleware(
+    State(pool): State<PgPool>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, AuthError> {
+    let claims = request
+        .extensions()
+        .get::<Claims>()
+        .filter(|claims| claims.act.is_none())
+        .ok_or(AuthError::AdminRequired)?;
+
+    let role: Option<(Role,)> =
+        sqlx::query_as("SELECT role FROM users WHERE id = $1 AND locked_at IS NULL")
+            .bind(claims.sub)
+            .fetch_optional(&pool)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+    if !matches!(role, Some((Role::Admin,))) {
+        return Err(AuthError::AdminRequired);
+    }
+    Ok(next.run(request).await)
+}
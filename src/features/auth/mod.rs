@@ -1,5 +1,12 @@
 pub(crate) mod handlers;
+pub mod keys;
 pub mod middlewares;
 pub(crate) mod models;
+pub mod oidc;
+pub mod password_policy;
 pub mod routes;
+pub mod scopes;
 pub mod services;
+pub mod throttle;
+pub mod totp;
+pub mod webauthn;
@@ -1,8 +1,8 @@
-use std::sync::LazyLock;
+use std::{convert::Infallible, net::SocketAddr};
 
 use axum::{
-    extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, StatusCode},
     response::{IntoResponse, Response},
     Json, RequestPartsExt,
 };
@@ -10,38 +10,122 @@ use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
 };
-use jsonwebtoken::{decode, DecodingKey, EncodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use validator::Validate;
 
+use super::keys::KEYS;
+
 #[derive(Debug, Serialize, Deserialize, Clone, Validate)]
 pub struct AuthCredentialsDto {
     #[validate(email(message = "Invalid email"))]
     pub email: String,
-    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
     pub password: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum AuthError {
     WrongCredentials,
+    EmailNotVerified,
     MissingCredentials,
     TokenCreationError,
     InvalidToken,
-    WeakPassword,
+    WeakPassword(PasswordRejection),
+    InvalidMfaCode,
+    MfaAlreadyEnabled,
+    MfaNotEnrolled,
+    MissingScope(Scope),
+    /// Carries the number of seconds to send in `Retry-After`.
+    TooManyAttempts(i64),
+    UnknownIdentityProvider,
+    InvalidOidcState,
+    InvalidOidcResponse,
+    UnverifiedIdentityEmail,
+    InvalidWebauthnResponse,
+    AccountLocked,
+    PasswordResetRequired,
+    AdminRequired,
+    SessionRequired,
+    InternalServerError,
+}
+
+/// Why a password was refused, with zxcvbn's feedback when it has any.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasswordRejection {
+    pub reason: String,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
 }
 
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
             AuthError::WrongCredentials => (StatusCode::UNAUTHORIZED, "wrong credentials"),
+            AuthError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email not verified"),
             AuthError::MissingCredentials => (StatusCode::BAD_REQUEST, "Missing crdentials"),
             AuthError::TokenCreationError => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Token creation error")
             }
             AuthError::InvalidToken => (StatusCode::BAD_REQUEST, "Invalid token"),
-            AuthError::WeakPassword => (StatusCode::BAD_REQUEST, "Weak password"),
+            AuthError::WeakPassword(rejection) => {
+                let body = Json(json!({
+                    "error": "Weak password",
+                    "reason": rejection.reason,
+                    "warning": rejection.warning,
+                    "suggestions": rejection.suggestions
+                }));
+                return (StatusCode::BAD_REQUEST, body).into_response();
+            }
+            AuthError::InvalidMfaCode => (StatusCode::UNAUTHORIZED, "Invalid MFA code"),
+            AuthError::MfaAlreadyEnabled => (StatusCode::CONFLICT, "MFA is already enabled"),
+            AuthError::MfaNotEnrolled => (StatusCode::BAD_REQUEST, "MFA is not enrolled"),
+            AuthError::MissingScope(scope) => {
+                let body = Json(json!({
+                    "error": "Insufficient scope",
+                    "required_scope": scope
+                }));
+                return (StatusCode::FORBIDDEN, body).into_response();
+            }
+            AuthError::TooManyAttempts(retry_after) => {
+                let body = Json(json!({
+                    "error": "Too many failed login attempts"
+                }));
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    body,
+                )
+                    .into_response();
+            }
+            AuthError::UnknownIdentityProvider => {
+                (StatusCode::NOT_FOUND, "Unknown identity provider")
+            }
+            AuthError::InvalidOidcState => {
+                (StatusCode::BAD_REQUEST, "Invalid or expired login state")
+            }
+            AuthError::InvalidOidcResponse => {
+                (StatusCode::UNAUTHORIZED, "Identity provider login failed")
+            }
+            AuthError::UnverifiedIdentityEmail => (
+                StatusCode::FORBIDDEN,
+                "Identity provider did not return a verified email",
+            ),
+            AuthError::InvalidWebauthnResponse => {
+                (StatusCode::UNAUTHORIZED, "Passkey verification failed")
+            }
+            AuthError::AccountLocked => (StatusCode::FORBIDDEN, "Account is locked"),
+            AuthError::PasswordResetRequired => (
+                StatusCode::FORBIDDEN,
+                "Password reset required, check your email for a reset link",
+            ),
+            AuthError::AdminRequired => (StatusCode::FORBIDDEN, "Admin role required"),
+            AuthError::SessionRequired => (
+                StatusCode::FORBIDDEN,
+                "Sign in as yourself to do this, not with a personal access token or while impersonating",
+            ),
+            AuthError::InternalServerError => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+            }
         };
         let body = Json(json!({
             "error": error_message
@@ -50,11 +134,78 @@ impl IntoResponse for AuthError {
     }
 }
 
+/// Registered JWT claims plus our own `email`, `scopes` and `role`.
+///
+/// Tokens issued before these claims existed carry the email as `company` and
+/// have no `iss`, `aud`, `iat` or `nbf`; they still deserialize.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
+    #[serde(with = "subject")]
     pub sub: i32,
-    pub company: String,
+    #[serde(alias = "company")]
+    pub email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
     pub exp: usize,
+    /// Id of the session row the token belongs to; absent on older tokens and
+    /// personal access tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// Tokens issued before scopes existed were all-or-nothing, so they keep
+    /// every scope.
+    #[serde(default = "Scope::all")]
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub role: Role,
+    /// The admin acting as `sub` while impersonating (RFC 8693 `act`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    /// Set by `jwt_middleware` for personal access tokens; never read from or
+    /// written to a JWT.
+    #[serde(skip)]
+    pub personal_access_token: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Actor {
+    #[serde(with = "subject")]
+    pub sub: i32,
+}
+
+/// `sub` is written as a string, as RFC 7519 requires, but older tokens carry
+/// the numeric user id.
+mod subject {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(sub: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&sub.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Subject {
+            Number(i32),
+            Text(String),
+        }
+
+        match Subject::deserialize(deserializer)? {
+            Subject::Number(sub) => Ok(sub),
+            Subject::Text(sub) => sub.parse().map_err(de::Error::custom),
+        }
+    }
+}
+
+impl Claims {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
 }
 
 impl<S> FromRequestParts<S> for Claims
@@ -67,12 +218,74 @@ where
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|_| AuthError::InvalidToken)?;
-        let token_data = decode(bearer.token(), &KEYS.decoding, &Validation::default())
-            .map_err(|_| AuthError::InvalidToken)?;
-        Ok(token_data.claims)
+        KEYS.decode(bearer.token())
+            .map_err(|_| AuthError::InvalidToken)
+    }
+}
+
+/// Where a login comes from, recorded on the session it creates.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Ok(Self { ip, user_agent })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "token_scope")]
+pub enum Scope {
+    #[serde(rename = "tasks:read")]
+    #[sqlx(rename = "tasks:read")]
+    TasksRead,
+    #[serde(rename = "tasks:write")]
+    #[sqlx(rename = "tasks:write")]
+    TasksWrite,
+    #[serde(rename = "users:read")]
+    #[sqlx(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    #[sqlx(rename = "users:write")]
+    UsersWrite,
+}
+
+impl Scope {
+    pub fn all() -> Vec<Scope> {
+        vec![
+            Scope::TasksRead,
+            Scope::TasksWrite,
+            Scope::UsersRead,
+            Scope::UsersWrite,
+        ]
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Admin,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthResponse {
     pub token: String,
@@ -88,20 +301,202 @@ impl AuthResponse {
     }
 }
 
-pub static KEYS: LazyLock<Keys> = LazyLock::new(|| {
-    let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    Keys::new(secret.as_bytes())
-});
-
-pub struct Keys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaChallenge {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    /// Second factors the user can complete the login with.
+    pub mfa_methods: Vec<MfaMethod>,
 }
 
-impl Keys {
-    fn new(secret: &[u8]) -> Self {
-        let encoding = EncodingKey::from_secret(secret);
-        let decoding = DecodingKey::from_secret(secret);
-        Self { encoding, decoding }
+impl MfaChallenge {
+    pub fn new(mfa_token: String, mfa_methods: Vec<MfaMethod>) -> Self {
+        Self {
+            mfa_required: true,
+            mfa_token,
+            mfa_methods,
+        }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MfaMethod {
+    Totp,
+    Webauthn,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    MfaRequired(MfaChallenge),
+}
+
+/// Short-lived claims proving the password step succeeded; only accepted by
+/// `POST /login/mfa` and `POST /login/mfa/webauthn`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MfaClaims {
+    #[serde(with = "subject")]
+    pub sub: i32,
+    pub iss: String,
+    pub aud: String,
+    pub mfa_pending: bool,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaLoginDto {
+    pub mfa_token: String,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct MagicLinkRequestDto {
+    #[validate(email(message = "Invalid email"))]
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkVerifyDto {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetPasswordDto {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcCallbackDto {
+    pub code: Option<String>,
+    pub state: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpCodeDto {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpEnrollmentResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaTokenDto {
+    pub mfa_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnUser {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PubKeyCredParam {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: String,
+    pub user_verification: String,
+}
+
+/// `publicKey` options for `navigator.credentials.create()`; binary fields
+/// are base64url encoded.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingParty,
+    pub user: WebauthnUser,
+    pub pub_key_cred_params: Vec<PubKeyCredParam>,
+    pub timeout: u64,
+    pub attestation: String,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+/// `publicKey` options for `navigator.credentials.get()`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialRequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: u64,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationResponseDto {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub attestation_object: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterPasskeyDto {
+    pub id: String,
+    pub response: AttestationResponseDto,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResponseDto {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasskeyAssertionDto {
+    pub id: String,
+    pub response: AssertionResponseDto,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MfaPasskeyDto {
+    pub mfa_token: String,
+    pub credential: PasskeyAssertionDto,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Passkey {
+    pub id: i32,
+    pub name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+}
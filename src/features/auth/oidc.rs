@@ -0,0 +1,574 @@
+use std::sync::LazyLock;
+
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use rand::{distr::Alphanumeric, Rng};
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::config::oidc_config::{OidcConfig, OidcProviderConfig};
+
+use super::models::AuthError;
+
+pub static OIDC_CONFIG: LazyLock<OidcConfig> =
+    LazyLock::new(|| OidcConfig::from_env().expect("Failed to load OIDC config"));
+
+const LOGIN_STATE_TTL_MINUTES: i64 = 10;
+const ID_TOKEN_LEEWAY_SECONDS: u64 = 60;
+
+/// The subset of the discovery document we use.
+#[derive(Debug, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub nonce: Option<String>,
+}
+
+/// The per-login secrets kept server side between the redirect and the
+/// callback.
+struct LoginState {
+    state: String,
+    nonce: String,
+    code_verifier: String,
+}
+
+impl LoginState {
+    fn generate() -> Self {
+        Self {
+            state: OidcService::random_string(32),
+            nonce: OidcService::random_string(32),
+            code_verifier: OidcService::random_string(64),
+        }
+    }
+}
+
+pub struct OidcService;
+
+impl OidcService {
+    /// Starts an authorization code + PKCE flow and returns the provider URL
+    /// to send the browser to.
+    pub async fn authorization_url(
+        pool: &PgPool,
+        provider_name: &str,
+    ) -> Result<String, AuthError> {
+        let provider = Self::provider(provider_name)?;
+        let metadata = Self::discover(provider).await?;
+        let login_state = LoginState::generate();
+        let now = chrono::Utc::now();
+
+        sqlx::query("DELETE FROM oidc_login_states WHERE created_at <= $1")
+            .bind(now - chrono::Duration::minutes(LOGIN_STATE_TTL_MINUTES))
+            .execute(pool)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO oidc_login_states (state, provider, code_verifier, nonce, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(&login_state.state)
+        .bind(&provider.name)
+        .bind(&login_state.code_verifier)
+        .bind(&login_state.nonce)
+        .bind(now)
+        .execute(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        Self::authorization_request_url(provider, &metadata, &login_state)
+    }
+
+    /// Redeems the authorization code and returns the validated ID token claims.
+    pub async fn complete_login(
+        pool: &PgPool,
+        provider_name: &str,
+        code: &str,
+        state: &str,
+    ) -> Result<IdTokenClaims, AuthError> {
+        let provider = Self::provider(provider_name)?;
+
+        // States are single use: consume it before talking to the provider.
+        let login_state: Option<(String, String)> = sqlx::query_as(
+            r#"
+            DELETE FROM oidc_login_states
+            WHERE state = $1 AND provider = $2 AND created_at > $3
+            RETURNING code_verifier, nonce
+            "#,
+        )
+        .bind(state)
+        .bind(&provider.name)
+        .bind(chrono::Utc::now() - chrono::Duration::minutes(LOGIN_STATE_TTL_MINUTES))
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+        let (code_verifier, nonce) = login_state.ok_or(AuthError::InvalidOidcState)?;
+
+        let metadata = Self::discover(provider).await?;
+        Self::redeem_code(provider, &metadata, code, &code_verifier, &nonce).await
+    }
+
+    fn authorization_request_url(
+        provider: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        login_state: &LoginState,
+    ) -> Result<String, AuthError> {
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", provider.client_id.as_str()),
+                ("redirect_uri", provider.redirect_uri.as_str()),
+                ("scope", "openid email profile"),
+                ("state", login_state.state.as_str()),
+                ("nonce", login_state.nonce.as_str()),
+                (
+                    "code_challenge",
+                    Self::pkce_challenge(&login_state.code_verifier).as_str(),
+                ),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| Self::provider_error("Invalid authorization endpoint", e))?;
+        Ok(url.to_string())
+    }
+
+    async fn redeem_code(
+        provider: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, AuthError> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &provider.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+
+        let token_response: TokenResponse = reqwest::Client::new()
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Self::provider_error("Token request failed", e))?
+            .json()
+            .await
+            .map_err(|e| Self::provider_error("Invalid token response", e))?;
+
+        Self::validate_id_token(provider, metadata, &token_response.id_token, nonce).await
+    }
+
+    pub fn pkce_challenge(code_verifier: &str) -> String {
+        BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()))
+    }
+
+    async fn validate_id_token(
+        provider: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, AuthError> {
+        let header =
+            decode_header(id_token).map_err(|e| Self::provider_error("Invalid ID token", e))?;
+        // Only asymmetric signatures can be checked against the provider's JWKS.
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(AuthError::InvalidOidcResponse);
+        }
+
+        let jwks: JwkSet = reqwest::get(&metadata.jwks_uri)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Self::provider_error("JWKS request failed", e))?
+            .json()
+            .await
+            .map_err(|e| Self::provider_error("Invalid JWKS", e))?;
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or(AuthError::InvalidOidcResponse)?;
+        let decoding_key =
+            DecodingKey::from_jwk(jwk).map_err(|e| Self::provider_error("Unsupported JWK", e))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&provider.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        validation.leeway = ID_TOKEN_LEEWAY_SECONDS;
+
+        let claims = decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+            .map_err(|e| Self::provider_error("ID token validation failed", e))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(AuthError::InvalidOidcResponse);
+        }
+        Ok(claims)
+    }
+
+    async fn discover(provider: &OidcProviderConfig) -> Result<ProviderMetadata, AuthError> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            provider.issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = reqwest::get(&url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Self::provider_error("Discovery request failed", e))?
+            .json()
+            .await
+            .map_err(|e| Self::provider_error("Invalid discovery document", e))?;
+
+        if metadata.issuer.trim_end_matches('/') != provider.issuer.trim_end_matches('/') {
+            return Err(AuthError::InvalidOidcResponse);
+        }
+        Ok(metadata)
+    }
+
+    fn provider(name: &str) -> Result<&'static OidcProviderConfig, AuthError> {
+        OIDC_CONFIG
+            .provider(name)
+            .ok_or(AuthError::UnknownIdentityProvider)
+    }
+
+    fn random_string(len: usize) -> String {
+        rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(len)
+            .map(char::from)
+            .collect()
+    }
+
+    fn provider_error(context: &str, err: impl std::fmt::Display) -> AuthError {
+        tracing::error!("OIDC: {}: {}", context, err);
+        AuthError::InvalidOidcResponse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{extract::State, http::StatusCode, routing::get, routing::post, Form, Json, Router};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const CLIENT_ID: &str = "todo-client";
+    const KID: &str = "mock-key";
+
+    /// What the provider has seen and what it will put in the next ID token.
+    struct MockState {
+        issuer: String,
+        code_challenge: Option<String>,
+        id_token_kid: String,
+        id_token_claims: Value,
+    }
+
+    /// An identity provider on a local port that signs ID tokens with a fresh
+    /// Ed25519 key and only redeems `good-code` with the matching verifier.
+    struct MockProvider {
+        config: OidcProviderConfig,
+        state: Arc<Mutex<MockState>>,
+        encoding_key: EncodingKey,
+    }
+
+    impl MockProvider {
+        async fn start() -> Self {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+            let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+            let jwks = json!({
+                "keys": [{
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "alg": "EdDSA",
+                    "use": "sig",
+                    "kid": KID,
+                    "x": BASE64URL_NOPAD.encode(key_pair.public_key().as_ref()),
+                }]
+            });
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let issuer = format!("http://{}", listener.local_addr().unwrap());
+            let state = Arc::new(Mutex::new(MockState {
+                issuer: issuer.clone(),
+                code_challenge: None,
+                id_token_kid: KID.to_string(),
+                id_token_claims: Value::Null,
+            }));
+            let encoding_key = EncodingKey::from_ed_der(pkcs8.as_ref());
+
+            let token_key = encoding_key.clone();
+            let app = Router::new()
+                .route(
+                    "/.well-known/openid-configuration",
+                    get(|State(state): State<Arc<Mutex<MockState>>>| async move {
+                        let issuer = state.lock().unwrap().issuer.clone();
+                        Json(json!({
+                            "issuer": issuer,
+                            "authorization_endpoint": format!("{}/authorize", issuer),
+                            "token_endpoint": format!("{}/token", issuer),
+                            "jwks_uri": format!("{}/jwks", issuer),
+                        }))
+                    }),
+                )
+                .route("/jwks", get(move || async move { Json(jwks) }))
+                .route(
+                    "/token",
+                    post(
+                        move |State(state): State<Arc<Mutex<MockState>>>,
+                              Form(form): Form<HashMap<String, String>>| async move {
+                            let state = state.lock().unwrap();
+                            let verifier_matches = state.code_challenge.as_deref()
+                                == form
+                                    .get("code_verifier")
+                                    .map(|verifier| OidcService::pkce_challenge(verifier))
+                                    .as_deref();
+                            if form.get("code").map(String::as_str) != Some("good-code")
+                                || !verifier_matches
+                            {
+                                return Err(StatusCode::BAD_REQUEST);
+                            }
+                            let mut header = Header::new(Algorithm::EdDSA);
+                            header.kid = Some(state.id_token_kid.clone());
+                            let id_token =
+                                encode(&header, &state.id_token_claims, &token_key).unwrap();
+                            Ok(Json(json!({ "id_token": id_token })))
+                        },
+                    ),
+                )
+                .with_state(state.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            Self {
+                config: OidcProviderConfig {
+                    name: "mock".to_string(),
+                    issuer,
+                    client_id: CLIENT_ID.to_string(),
+                    client_secret: None,
+                    redirect_uri: "http://localhost:3000/login/oidc/mock/callback".to_string(),
+                },
+                state,
+                encoding_key,
+            }
+        }
+
+        /// Runs the browser half of the flow: builds the authorization URL,
+        /// checks what it asks for and remembers the PKCE challenge.
+        async fn authorize(&self, login_state: &LoginState) -> ProviderMetadata {
+            let metadata = OidcService::discover(&self.config).await.unwrap();
+            let url = OidcService::authorization_request_url(&self.config, &metadata, login_state)
+                .unwrap();
+            let params: HashMap<String, String> = Url::parse(&url)
+                .unwrap()
+                .query_pairs()
+                .into_owned()
+                .collect();
+
+            assert!(url.starts_with(&metadata.authorization_endpoint));
+            assert_eq!(params["client_id"], CLIENT_ID);
+            assert_eq!(params["state"], login_state.state);
+            assert_eq!(params["nonce"], login_state.nonce);
+            assert_eq!(params["code_challenge_method"], "S256");
+            assert_ne!(params["code_challenge"], login_state.code_verifier);
+            self.state.lock().unwrap().code_challenge = Some(params["code_challenge"].clone());
+            metadata
+        }
+
+        fn issue(&self, claims: Value) {
+            self.state.lock().unwrap().id_token_claims = claims;
+        }
+
+        fn claims(&self, nonce: &str) -> Value {
+            let now = chrono::Utc::now().timestamp();
+            json!({
+                "iss": self.config.issuer,
+                "aud": CLIENT_ID,
+                "sub": "provider-user-1",
+                "email": "sso@example.com",
+                "email_verified": true,
+                "nonce": nonce,
+                "iat": now,
+                "exp": now + 300,
+            })
+        }
+
+        async fn redeem(
+            &self,
+            metadata: &ProviderMetadata,
+            login_state: &LoginState,
+        ) -> Result<IdTokenClaims, AuthError> {
+            OidcService::redeem_code(
+                &self.config,
+                metadata,
+                "good-code",
+                &login_state.code_verifier,
+                &login_state.nonce,
+            )
+            .await
+        }
+    }
+
+    #[tokio::test]
+    async fn state_and_pkce_round_trip() {
+        let provider = MockProvider::start().await;
+        let login_state = LoginState::generate();
+        let metadata = provider.authorize(&login_state).await;
+        provider.issue(provider.claims(&login_state.nonce));
+
+        let claims = provider.redeem(&metadata, &login_state).await.unwrap();
+        assert_eq!(claims.sub, "provider-user-1");
+        assert_eq!(claims.email.as_deref(), Some("sso@example.com"));
+        assert!(claims.email_verified);
+
+        // The provider refuses the code with any other verifier.
+        let wrong_verifier = OidcService::redeem_code(
+            &provider.config,
+            &metadata,
+            "good-code",
+            &LoginState::generate().code_verifier,
+            &login_state.nonce,
+        )
+        .await;
+        assert!(matches!(
+            wrong_verifier,
+            Err(AuthError::InvalidOidcResponse)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_nonce_mismatch() {
+        let provider = MockProvider::start().await;
+        let login_state = LoginState::generate();
+        let metadata = provider.authorize(&login_state).await;
+        provider.issue(provider.claims("some-other-nonce"));
+
+        let result = provider.redeem(&metadata, &login_state).await;
+        assert!(matches!(result, Err(AuthError::InvalidOidcResponse)));
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_audience() {
+        let provider = MockProvider::start().await;
+        let login_state = LoginState::generate();
+        let metadata = provider.authorize(&login_state).await;
+        let mut claims = provider.claims(&login_state.nonce);
+        claims["aud"] = json!("another-client");
+        provider.issue(claims);
+
+        let result = provider.redeem(&metadata, &login_state).await;
+        assert!(matches!(result, Err(AuthError::InvalidOidcResponse)));
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_issuer() {
+        let provider = MockProvider::start().await;
+        let login_state = LoginState::generate();
+        let metadata = provider.authorize(&login_state).await;
+        let mut claims = provider.claims(&login_state.nonce);
+        claims["iss"] = json!("https://evil.example.com");
+        provider.issue(claims);
+
+        let result = provider.redeem(&metadata, &login_state).await;
+        assert!(matches!(result, Err(AuthError::InvalidOidcResponse)));
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_id_token() {
+        let provider = MockProvider::start().await;
+        let login_state = LoginState::generate();
+        let metadata = provider.authorize(&login_state).await;
+        let mut claims = provider.claims(&login_state.nonce);
+        claims["exp"] = json!(chrono::Utc::now().timestamp() - 2 * ID_TOKEN_LEEWAY_SECONDS as i64);
+        provider.issue(claims);
+
+        let result = provider.redeem(&metadata, &login_state).await;
+        assert!(matches!(result, Err(AuthError::InvalidOidcResponse)));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_key_id() {
+        let provider = MockProvider::start().await;
+        let login_state = LoginState::generate();
+        let metadata = provider.authorize(&login_state).await;
+        provider.issue(provider.claims(&login_state.nonce));
+        provider.state.lock().unwrap().id_token_kid = "rotated-away".to_string();
+
+        let result = provider.redeem(&metadata, &login_state).await;
+        assert!(matches!(result, Err(AuthError::InvalidOidcResponse)));
+    }
+
+    #[tokio::test]
+    async fn accepts_id_token_signed_by_provider_key_only() {
+        let provider = MockProvider::start().await;
+        let login_state = LoginState::generate();
+        let metadata = provider.authorize(&login_state).await;
+        let claims = provider.claims(&login_state.nonce);
+
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(KID.to_string());
+        let genuine = encode(&header, &claims, &provider.encoding_key).unwrap();
+        assert!(OidcService::validate_id_token(
+            &provider.config,
+            &metadata,
+            &genuine,
+            &login_state.nonce
+        )
+        .await
+        .is_ok());
+
+        let other_pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let forged = encode(
+            &header,
+            &claims,
+            &EncodingKey::from_ed_der(other_pkcs8.as_ref()),
+        )
+        .unwrap();
+        let result = OidcService::validate_id_token(
+            &provider.config,
+            &metadata,
+            &forged,
+            &login_state.nonce,
+        )
+        .await;
+        assert!(matches!(result, Err(AuthError::InvalidOidcResponse)));
+    }
+}
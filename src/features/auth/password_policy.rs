@@ -0,0 +1,259 @@
+use std::{path::Path, sync::LazyLock};
+
+use sha1::{Digest, Sha1};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, BufReader},
+};
+
+use crate::config::password_policy_config::PasswordPolicyConfig;
+
+use super::models::{AuthError, PasswordRejection};
+
+pub static PASSWORD_POLICY_CONFIG: LazyLock<PasswordPolicyConfig> = LazyLock::new(|| {
+    PasswordPolicyConfig::from_env().expect("Failed to load password policy config")
+});
+
+pub struct PasswordPolicy;
+
+impl PasswordPolicy {
+    /// Checks length, zxcvbn strength and, when configured, the breached
+    /// password list. `user_inputs` (the email, for instance) are words the
+    /// password should not be built from.
+    pub async fn check(password: &str, user_inputs: &[&str]) -> Result<(), AuthError> {
+        let config = &*PASSWORD_POLICY_CONFIG;
+        Self::check_strength(config, password, user_inputs).map_err(AuthError::WeakPassword)?;
+
+        if let Some(path) = &config.breached_passwords_path {
+            let breached = Self::is_breached(Path::new(path), password)
+                .await
+                .map_err(|err| {
+                    tracing::error!("Failed to read breached password list: {}", err);
+                    AuthError::InternalServerError
+                })?;
+            if breached {
+                return Err(AuthError::WeakPassword(PasswordRejection {
+                    reason: "Password has appeared in a data breach".to_string(),
+                    warning: None,
+                    suggestions: vec!["Choose a password you have not used elsewhere.".to_string()],
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_strength(
+        config: &PasswordPolicyConfig,
+        password: &str,
+        user_inputs: &[&str],
+    ) -> Result<(), PasswordRejection> {
+        let length = password.chars().count();
+        if length < config.min_length || length > config.max_length {
+            return Err(PasswordRejection {
+                reason: format!(
+                    "Password must be between {} and {} characters",
+                    config.min_length, config.max_length
+                ),
+                warning: None,
+                suggestions: Vec::new(),
+            });
+        }
+
+        let estimate = zxcvbn::zxcvbn(password, user_inputs);
+        if u8::from(estimate.score()) < config.min_score {
+            let feedback = estimate.feedback();
+            return Err(PasswordRejection {
+                reason: "Password is too weak".to_string(),
+                warning: feedback
+                    .and_then(|feedback| feedback.warning())
+                    .map(|warning| warning.to_string()),
+                suggestions: feedback
+                    .map(|feedback| {
+                        feedback
+                            .suggestions()
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_else(|| vec!["Add another word or two.".to_string()]),
+            });
+        }
+        Ok(())
+    }
+
+    /// Looks the password's SHA-1 up in a local HIBP-format list. With a
+    /// directory only the one range file for the 5-character prefix is read.
+    pub async fn is_breached(path: &Path, password: &str) -> Result<bool, std::io::Error> {
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        let (file, needle) = if fs::metadata(path).await?.is_dir() {
+            match fs::File::open(path.join(format!("{}.txt", prefix))).await {
+                Ok(file) => (file, suffix),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        } else {
+            (fs::File::open(path).await?, hash.as_str())
+        };
+
+        let mut lines = BufReader::new(file).lines();
+        while let Some(line) = lines.next_line().await? {
+            let candidate = line.split(':').next().unwrap_or_default().trim();
+            if candidate.eq_ignore_ascii_case(needle) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // SHA-1 of "password".
+    const PASSWORD_SHA1: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+    fn config(min_score: u8) -> PasswordPolicyConfig {
+        PasswordPolicyConfig {
+            min_score,
+            min_length: 8,
+            max_length: 64,
+            breached_passwords_path: None,
+        }
+    }
+
+    /// A fresh directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "password-policy-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn enforces_length_bounds_in_characters() {
+        let config = config(0);
+        let too_short = PasswordPolicy::check_strength(&config, "seven77", &[]).unwrap_err();
+        assert_eq!(
+            too_short.reason,
+            "Password must be between 8 and 64 characters"
+        );
+        assert!(PasswordPolicy::check_strength(&config, &"x".repeat(65), &[]).is_err());
+        assert!(PasswordPolicy::check_strength(&config, &"x".repeat(64), &[]).is_ok());
+        // Eight characters, sixteen bytes.
+        assert!(PasswordPolicy::check_strength(&config, "ééééééééé", &[]).is_ok());
+        assert!(PasswordPolicy::check_strength(&config, "éééééé", &[]).is_err());
+    }
+
+    #[test]
+    fn gates_on_min_score() {
+        let moderate = "Summer2024!";
+        let score = u8::from(zxcvbn::zxcvbn(moderate, &[]).score());
+        assert!(score < 4);
+        assert!(PasswordPolicy::check_strength(&config(score), moderate, &[]).is_ok());
+        assert!(PasswordPolicy::check_strength(&config(score + 1), moderate, &[]).is_err());
+
+        let passphrase = "correct horse battery staple orbit";
+        assert!(PasswordPolicy::check_strength(&config(4), passphrase, &[]).is_ok());
+    }
+
+    #[test]
+    fn returns_zxcvbn_feedback() {
+        let rejection = PasswordPolicy::check_strength(&config(3), "password1", &[]).unwrap_err();
+        assert_eq!(rejection.reason, "Password is too weak");
+        assert!(rejection.warning.is_some());
+        assert!(!rejection.suggestions.is_empty());
+    }
+
+    #[test]
+    fn counts_user_inputs_against_the_password() {
+        let password = "mwangi.okonkwo";
+        assert!(PasswordPolicy::check_strength(&config(4), password, &[]).is_ok());
+        assert!(
+            PasswordPolicy::check_strength(&config(4), password, &["mwangi", "okonkwo"]).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn looks_up_range_files_by_prefix() {
+        let dir = TempDir::new("range");
+        let (prefix, suffix) = PASSWORD_SHA1.split_at(5);
+        std::fs::write(
+            dir.0.join(format!("{}.txt", prefix)),
+            format!(
+                "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n{}:9545824\r\n",
+                suffix
+            ),
+        )
+        .unwrap();
+
+        assert!(PasswordPolicy::is_breached(&dir.0, "password")
+            .await
+            .unwrap());
+        // Same range file, different suffix.
+        std::fs::write(
+            dir.0.join(format!("{}.txt", prefix)),
+            "0018A45C4D1DEF81644B54AB7F969B88D65:1\n",
+        )
+        .unwrap();
+        assert!(!PasswordPolicy::is_breached(&dir.0, "password")
+            .await
+            .unwrap());
+        // No range file for this prefix.
+        assert!(!PasswordPolicy::is_breached(&dir.0, "not in the list")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn looks_up_full_hashes_in_a_single_file() {
+        let dir = TempDir::new("single");
+        let file = dir.0.join("pwned.txt");
+        std::fs::write(
+            &file,
+            format!(
+                "7C4A8D09CA3762AF61E59520943DC26494F8941B:1\n{}:3\n",
+                PASSWORD_SHA1.to_lowercase()
+            ),
+        )
+        .unwrap();
+
+        assert!(PasswordPolicy::is_breached(&file, "password")
+            .await
+            .unwrap());
+        assert!(PasswordPolicy::is_breached(&file, "123456").await.unwrap());
+        assert!(
+            !PasswordPolicy::is_breached(&file, "correct horse battery staple")
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_when_the_list_is_missing() {
+        let dir = TempDir::new("missing");
+        assert!(
+            PasswordPolicy::is_breached(&dir.0.join("nope.txt"), "password")
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,11 +1,46 @@
-use super::handlers;
-use axum::{routing::post, Router};
+use super::{handlers, middlewares::jwt_middleware};
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
 use sqlx::PgPool;
 
 pub fn auth_routes(pool: PgPool) -> Router {
+    let mfa_routes = Router::new()
+        .route("/mfa/totp/enroll", post(handlers::enroll_totp))
+        .route("/mfa/totp/confirm", post(handlers::confirm_totp))
+        .route(
+            "/mfa/webauthn/register/options",
+            post(handlers::passkey_registration_options),
+        )
+        .route("/mfa/webauthn/register", post(handlers::register_passkey))
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware));
+
     Router::new()
         .route("/login", post(handlers::login))
+        .route("/login/mfa", post(handlers::login_mfa))
+        .route(
+            "/login/mfa/webauthn/options",
+            post(handlers::mfa_passkey_options),
+        )
+        .route("/login/mfa/webauthn", post(handlers::login_mfa_passkey))
+        .route(
+            "/login/webauthn/options",
+            post(handlers::passkey_login_options),
+        )
+        .route("/login/webauthn", post(handlers::passkey_login))
+        .route("/login/magic", post(handlers::request_magic_link))
+        .route("/login/magic/verify", post(handlers::verify_magic_link))
+        .route("/login/oidc/{provider}", get(handlers::oidc_login))
+        .route(
+            "/login/oidc/{provider}/callback",
+            get(handlers::oidc_callback),
+        )
         .route("/register", post(handlers::register))
         .route("/verify", post(handlers::verify_email))
+        .route("/password/reset", post(handlers::reset_password))
+        .route("/.well-known/jwks.json", get(handlers::jwks))
+        .merge(mfa_routes)
         .with_state(pool)
 }
@@ -0,0 +1,79 @@
+use std::marker::PhantomData;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+use super::models::{AuthError, Claims, Scope};
+
+/// Marker for a scope that a route requires, used as `RequireScope<TasksWrite>`.
+pub trait RequiredScope {
+    const SCOPE: Scope;
+}
+
+pub struct TasksRead;
+pub struct TasksWrite;
+pub struct UsersRead;
+pub struct UsersWrite;
+
+impl RequiredScope for TasksRead {
+    const SCOPE: Scope = Scope::TasksRead;
+}
+
+impl RequiredScope for TasksWrite {
+    const SCOPE: Scope = Scope::TasksWrite;
+}
+
+impl RequiredScope for UsersRead {
+    const SCOPE: Scope = Scope::UsersRead;
+}
+
+impl RequiredScope for UsersWrite {
+    const SCOPE: Scope = Scope::UsersWrite;
+}
+
+/// Rejects the request with 403 unless the `Claims` inserted by
+/// `jwt_middleware` carry the scope `S`.
+pub struct RequireScope<S: RequiredScope>(PhantomData<S>);
+
+impl<S, T> FromRequestParts<T> for RequireScope<S>
+where
+    S: RequiredScope,
+    T: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &T) -> Result<Self, Self::Rejection> {
+        let claims = parts
+            .extensions
+            .get::<Claims>()
+            .ok_or(AuthError::InvalidToken)?;
+
+        if !claims.has_scope(S::SCOPE) {
+            return Err(AuthError::MissingScope(S::SCOPE));
+        }
+        Ok(RequireScope(PhantomData))
+    }
+}
+
+/// Rejects the request with 403 unless the user signed in themselves: a
+/// personal access token or an admin impersonating them can't mint more
+/// credentials, change how the account signs in or delete it.
+pub struct RequireSession;
+
+impl<T> FromRequestParts<T> for RequireSession
+where
+    T: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &T) -> Result<Self, Self::Rejection> {
+        let claims = parts
+            .extensions
+            .get::<Claims>()
+            .ok_or(AuthError::InvalidToken)?;
+
+        if claims.personal_access_token || claims.act.is_some() {
+            return Err(AuthError::SessionRequired);
+        }
+        Ok(RequireSession)
+    }
+}
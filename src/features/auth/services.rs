@@ -1,26 +1,80 @@
-use crate::features::users::models::User;
+use std::sync::LazyLock;
 
-use super::models::{AuthCredentialsDto, AuthResponse, Claims, KEYS};
+use crate::{
+    config::password_hash_config::PasswordHashConfig,
+    features::{users::models::User, workspaces::services::WorkspaceService},
+    shared::{mailer, time},
+};
+
+use super::{
+    keys::KEYS,
+    models::{
+        Actor, AuthCredentialsDto, AuthError, AuthResponse, Claims, ClientInfo, LoginResponse,
+        MfaChallenge, MfaClaims, MfaMethod, Role, Scope, TotpEnrollmentResponse,
+    },
+    oidc::IdTokenClaims,
+    password_policy::PasswordPolicy,
+    totp,
+    webauthn::WebauthnService,
+};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
+    Algorithm, Argon2, KeyId, Params, ParamsBuilder, Version,
 };
-use jsonwebtoken::{decode, encode, Header, Validation};
-use lettre::{transport::stub::StubTransport, Transport};
 use rand::{distr::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use validator::{Validate, ValidationErrors};
+
+pub static PASSWORD_HASH_CONFIG: LazyLock<PasswordHashConfig> =
+    LazyLock::new(|| PasswordHashConfig::from_env().expect("Failed to load password hash config"));
+
+static DUMMY_PASSWORD_HASH: LazyLock<String> = LazyLock::new(|| {
+    AuthService::hash_password_blocking("dummy password for unknown emails")
+        .expect("Failed to hash dummy password")
+});
+
+/// `keyid` written into hashes made with the pepper, so hashes from before the
+/// pepper was configured can still be verified and then upgraded.
+const PEPPER_KEY_ID: &[u8] = b"pepper";
+
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "pat_";
+const RECOVERY_CODE_COUNT: usize = 10;
+const MFA_TOKEN_TTL_MINUTES: i64 = 5;
+const MAGIC_LINK_TTL_MINUTES: i64 = 15;
+/// Magic links a user can request per `MAGIC_LINK_TTL_MINUTES`.
+const MAGIC_LINK_MAX_REQUESTS: i64 = 3;
+const SESSION_LAST_SEEN_INTERVAL_MINUTES: i64 = 1;
+const SESSION_TTL_DAYS: i64 = 14;
+const PASSWORD_RESET_TTL_HOURS: i64 = 24;
 pub struct AuthService;
 
 impl AuthService {
-    pub async fn find_user(pool: &PgPool, email: &str, password: &str) -> Result<User, String> {
-        let user = Self::find_user_by_email(pool, email)
-            .await
-            .map_err(|e| e.to_string())?;
-        Self::verify_password(password, &user.password).map_err(|e| e.to_string())?;
+    /// Unknown emails and wrong passwords fail identically, including timing:
+    /// an unknown email is still verified against a dummy hash.
+    pub async fn find_user(pool: &PgPool, email: &str, password: &str) -> Result<User, AuthError> {
+        let user = Self::find_user_by_email(pool, email).await.ok();
+        let hash = user
+            .as_ref()
+            .map_or(DUMMY_PASSWORD_HASH.as_str(), |user| user.password.as_str());
+        let password_matches = Self::verify_password(password, hash).await.is_ok();
+
+        let user = user
+            .filter(|_| password_matches)
+            .ok_or(AuthError::WrongCredentials)?;
+
+        if Self::needs_rehash(&user.password) {
+            if let Err(err) = Self::rehash_password(pool, user.id, password).await {
+                tracing::error!("Failed to upgrade password hash: {}", err);
+            }
+        }
 
+        // Only reachable with the right password, so it reveals nothing new.
         if !user.verified {
-            return Err("Email not verified".to_string());
+            return Err(AuthError::EmailNotVerified);
+        }
+        if user.password_reset_required {
+            return Err(AuthError::PasswordResetRequired);
         }
         Ok(user)
     }
@@ -29,7 +83,8 @@ impl AuthService {
         let user = sqlx::query_as!(
             User,
             r#"
-                SELECT id, email, password, created_at, verified as "verified!", verification_token
+                SELECT id, email, password, verified as "verified!", role as "role: Role", locked_at,
+                    password_reset_required
                 FROM users
                 WHERE email = $1
                 "#,
@@ -41,6 +96,189 @@ impl AuthService {
         Ok(user)
     }
 
+    pub async fn find_user_by_id(pool: &PgPool, user_id: i32) -> Result<User, String> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+                SELECT id, email, password, verified as "verified!", role as "role: Role", locked_at,
+                    password_reset_required
+                FROM users
+                WHERE id = $1
+                "#,
+            user_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(user)
+    }
+
+    /// Finds the user linked to an external identity, linking it by verified
+    /// email (and creating the user if needed) on first sign-in.
+    pub async fn find_or_link_oidc_user(
+        pool: &PgPool,
+        provider: &str,
+        claims: &IdTokenClaims,
+    ) -> Result<User, AuthError> {
+        let linked = sqlx::query_as!(
+            User,
+            r#"
+            SELECT u.id, u.email, u.password, u.verified as "verified!", u.role as "role: Role",
+                u.locked_at, u.password_reset_required
+            FROM users u
+            JOIN user_identities i ON i.user_id = u.id
+            WHERE i.provider = $1 AND i.subject = $2
+            "#,
+            provider,
+            claims.sub
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+        if let Some(user) = linked {
+            return Ok(user);
+        }
+
+        let email = claims
+            .email
+            .as_deref()
+            .filter(|_| claims.email_verified)
+            .ok_or(AuthError::UnverifiedIdentityEmail)?;
+        // SSO-only accounts get a random password nobody knows.
+        let unusable_password = Self::hash_password(&Self::generate_personal_access_token())
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+        let now = chrono::Utc::now();
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        let existing = sqlx::query!(
+            r#"
+            SELECT id, verified as "verified!"
+            FROM users
+            WHERE email = $1
+            "#,
+            email
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        let user_id = match existing {
+            Some(user) if user.verified => user.id,
+            Some(user) => {
+                // Nobody proved ownership of this email before, so whoever set
+                // the account up may not own it: the provider's verified email
+                // takes it over and every credential they left behind goes.
+                sqlx::query!(
+                    r#"
+                    UPDATE users
+                    SET verified = TRUE, verification_token = NULL, password = $1,
+                        totp_secret = NULL, totp_enabled = FALSE, totp_last_step = NULL
+                    WHERE id = $2
+                    "#,
+                    unusable_password,
+                    user.id
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(|_| AuthError::InternalServerError)?;
+                for table in [
+                    "sessions",
+                    "personal_access_tokens",
+                    "recovery_codes",
+                    "webauthn_credentials",
+                    "webauthn_challenges",
+                    "user_identities",
+                    "magic_link_tokens",
+                    "password_reset_tokens",
+                ] {
+                    sqlx::query(&format!("DELETE FROM {} WHERE user_id = $1", table))
+                        .bind(user.id)
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(|_| AuthError::InternalServerError)?;
+                }
+                user.id
+            }
+            None => {
+                let user_id = sqlx::query!(
+                    r#"
+                    INSERT INTO users (email, password, created_at, verified)
+                    VALUES ($1, $2, $3, TRUE)
+                    RETURNING id
+                    "#,
+                    email,
+                    unusable_password,
+                    now
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|_| AuthError::InternalServerError)?
+                .id;
+                WorkspaceService::create_personal_workspace(&mut tx, user_id)
+                    .await
+                    .map_err(|_| AuthError::InternalServerError)?;
+                user_id
+            }
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_identities (user_id, provider, subject, email, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            user_id,
+            provider,
+            claims.sub,
+            email,
+            now
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        tx.commit()
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        Self::find_user_by_id(pool, user_id)
+            .await
+            .map_err(|_| AuthError::InternalServerError)
+    }
+
+    /// Issues the token for a user whose first factor succeeded, or an MFA
+    /// challenge when TOTP or a passkey is enrolled.
+    pub async fn complete_login(
+        pool: &PgPool,
+        user: &User,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse, AuthError> {
+        Self::ensure_not_locked(user)?;
+
+        let mut mfa_methods = Vec::new();
+        if Self::is_totp_enabled(pool, user.id)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?
+        {
+            mfa_methods.push(MfaMethod::Totp);
+        }
+        if WebauthnService::has_credentials(pool, user.id).await? {
+            mfa_methods.push(MfaMethod::Webauthn);
+        }
+        if !mfa_methods.is_empty() {
+            let challenge = Self::create_mfa_challenge(user, mfa_methods)
+                .map_err(|_| AuthError::TokenCreationError)?;
+            return Ok(LoginResponse::MfaRequired(challenge));
+        }
+
+        let auth_response = Self::create_auth_response(pool, user, client).await?;
+        Ok(LoginResponse::Authenticated(auth_response))
+    }
+
     pub async fn create_user(
         pool: &PgPool,
         email: &str,
@@ -48,29 +286,33 @@ impl AuthService {
     ) -> Result<(), sqlx::Error> {
         let created_at = chrono::Utc::now();
 
-        let password_hash =
-            Self::hash_password(password).map_err(|e| sqlx::Error::Protocol(e.into()))?;
+        let password_hash = Self::hash_password(password)
+            .await
+            .map_err(sqlx::Error::Protocol)?;
 
         let verification_token = Self::generate_verification_token();
 
-        sqlx::query!(
+        let mut tx = pool.begin().await?;
+        let user = sqlx::query!(
             r#"
                     INSERT INTO users (email, password, created_at, verification_token)
                     VALUES ($1, $2, $3, $4)
-                    RETURNING id, email, password, created_at
+                    RETURNING id
                     "#,
             email,
             password_hash,
             created_at,
             verification_token
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await
         .map_err(|_| sqlx::Error::RowNotFound)?;
+        WorkspaceService::create_personal_workspace(&mut tx, user.id).await?;
+        tx.commit().await?;
 
         Self::send_verification_email(email, &verification_token)
             .await
-            .map_err(|e| sqlx::Error::Protocol(e.into()))?;
+            .map_err(sqlx::Error::Protocol)?;
         Ok(())
     }
 
@@ -80,12 +322,13 @@ impl AuthService {
         auth_credentials_dto.validate()
     }
 
-    pub fn check_password_strength(password: &str) -> Result<(), String> {
-        let estimate = zxcvbn::zxcvbn(&password, &[]);
-        if estimate.score() < zxcvbn::Score::Three {
-            return Err("Password is too weak".to_string());
+    /// Words a password should not be built from: the email and its local part.
+    pub fn password_user_inputs(email: &str) -> Vec<&str> {
+        let mut user_inputs = vec![email];
+        if let Some((local_part, _)) = email.split_once('@') {
+            user_inputs.push(local_part);
         }
-        Ok(())
+        user_inputs
     }
 
     pub async fn email_exists(pool: &PgPool, email: &str) -> Result<bool, sqlx::Error> {
@@ -103,39 +346,278 @@ impl AuthService {
         Ok(result.is_some())
     }
 
-    pub fn hash_password(password: &str) -> Result<String, String> {
+    /// Hashes on the blocking pool; Argon2 would otherwise stall the executor.
+    pub async fn hash_password(password: &str) -> Result<String, String> {
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || Self::hash_password_blocking(&password))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    pub async fn verify_password(password: &str, hash: &str) -> Result<(), String> {
+        let (password, hash) = (password.to_string(), hash.to_string());
+        tokio::task::spawn_blocking(move || Self::verify_password_blocking(&password, &hash))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    pub fn hash_password_blocking(password: &str) -> Result<String, String> {
         let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
+        let peppered = PASSWORD_HASH_CONFIG.pepper.is_some();
 
-        argon2
+        Self::argon2(peppered)?
             .hash_password(password.as_bytes(), &salt)
             .map(|hash| hash.to_string())
             .map_err(|e| e.to_string())
     }
 
-    pub fn verify_password(password: &str, hash: &str) -> Result<(), String> {
+    pub fn verify_password_blocking(password: &str, hash: &str) -> Result<(), String> {
         let parsed_hash = PasswordHash::new(hash).map_err(|e| e.to_string())?;
-        Argon2::default()
+        // The costs are read from the hash itself; only the pepper has to match.
+        let peppered = parsed_hash.params.get("keyid").is_some();
+        Self::argon2(peppered)?
             .verify_password(password.as_bytes(), &parsed_hash)
             .map_err(|e| e.to_string())
     }
 
-    pub fn create_auth_response(user: &User) -> Result<AuthResponse, String> {
-        let exp = (chrono::Utc::now() + chrono::Duration::days(14)).timestamp() as usize;
+    /// Whether a stored hash was made with other costs, another algorithm or
+    /// without the current pepper.
+    pub fn needs_rehash(hash: &str) -> bool {
+        let Ok(parsed_hash) = PasswordHash::new(hash) else {
+            return false;
+        };
+        let Ok(params) = Params::try_from(&parsed_hash) else {
+            return true;
+        };
+        let config = &*PASSWORD_HASH_CONFIG;
+
+        parsed_hash.algorithm != Algorithm::Argon2id.ident()
+            || parsed_hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != config.memory_kib
+            || params.t_cost() != config.iterations
+            || params.p_cost() != config.parallelism
+            || params.keyid().is_empty() == config.pepper.is_some()
+    }
+
+    async fn rehash_password(pool: &PgPool, user_id: i32, password: &str) -> Result<(), String> {
+        let password_hash = Self::hash_password(password).await?;
+        sqlx::query("UPDATE users SET password = $1 WHERE id = $2")
+            .bind(password_hash)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn argon2(peppered: bool) -> Result<Argon2<'static>, String> {
+        let config = &*PASSWORD_HASH_CONFIG;
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(config.memory_kib)
+            .t_cost(config.iterations)
+            .p_cost(config.parallelism);
+        if peppered {
+            builder.keyid(KeyId::new(PEPPER_KEY_ID).map_err(|e| e.to_string())?);
+        }
+        let params = builder.build().map_err(|e| e.to_string())?;
+
+        match (peppered, &config.pepper) {
+            (false, _) => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+            (true, Some(pepper)) => Argon2::new_with_secret(
+                pepper.as_bytes(),
+                Algorithm::Argon2id,
+                Version::V0x13,
+                params,
+            )
+            .map_err(|e| e.to_string()),
+            (true, None) => {
+                Err("Password hash is peppered but no pepper is configured".to_string())
+            }
+        }
+    }
+
+    /// Records a session for the login and issues a token bound to it by `jti`.
+    pub async fn create_auth_response(
+        pool: &PgPool,
+        user: &User,
+        client: &ClientInfo,
+    ) -> Result<AuthResponse, AuthError> {
+        Self::ensure_not_locked(user)?;
+        Self::create_session_token(
+            pool,
+            user,
+            client,
+            chrono::Duration::days(SESSION_TTL_DAYS),
+            None,
+        )
+        .await
+    }
+
+    /// Issues a token for `user` on behalf of an admin. The session shows up in
+    /// the user's session list like any other and can be revoked there.
+    pub async fn create_impersonation_response(
+        pool: &PgPool,
+        user: &User,
+        admin_id: i32,
+        client: &ClientInfo,
+        ttl: chrono::Duration,
+    ) -> Result<AuthResponse, AuthError> {
+        Self::create_session_token(pool, user, client, ttl, Some(Actor { sub: admin_id })).await
+    }
+
+    async fn create_session_token(
+        pool: &PgPool,
+        user: &User,
+        client: &ClientInfo,
+        ttl: chrono::Duration,
+        act: Option<Actor>,
+    ) -> Result<AuthResponse, AuthError> {
+        let now = chrono::Utc::now();
+        let jti = Self::generate_verification_token();
+
+        sqlx::query(
+            r#"
+            INSERT INTO sessions (user_id, jti, user_agent, ip_address, created_at, last_seen_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            "#,
+        )
+        .bind(user.id)
+        .bind(&jti)
+        .bind(&client.user_agent)
+        .bind(&client.ip)
+        .bind(now)
+        .execute(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
         let claims = Claims {
             sub: user.id,
-            company: user.email.clone(),
-            exp,
+            email: user.email.clone(),
+            iss: Some(KEYS.issuer.clone()),
+            aud: Some(KEYS.audience.clone()),
+            iat: Some(now.timestamp() as usize),
+            nbf: Some(now.timestamp() as usize),
+            exp: (now + ttl).timestamp() as usize,
+            jti: Some(jti),
+            scopes: Scope::all(),
+            role: user.role,
+            act,
+            personal_access_token: false,
         };
-        let header = Header::default();
-        let token = encode(&header, &claims, &KEYS.encoding).map_err(|e| e.to_string())?;
+        let token = KEYS
+            .encode(&claims)
+            .map_err(|_| AuthError::TokenCreationError)?;
         Ok(AuthResponse::new(token))
     }
 
+    fn ensure_not_locked(user: &User) -> Result<(), AuthError> {
+        match user.locked_at {
+            Some(_) => Err(AuthError::AccountLocked),
+            None => Ok(()),
+        }
+    }
+
+    /// Rejects tokens whose session was revoked and bumps `last_seen_at`, at
+    /// most once a minute. Tokens issued before sessions existed have no `jti`
+    /// and can't be revoked, so they only pass while legacy tokens are accepted.
+    pub async fn validate_session(pool: &PgPool, claims: &Claims) -> Result<(), String> {
+        let Some(jti) = &claims.jti else {
+            return match KEYS.accept_legacy_tokens {
+                true => Ok(()),
+                false => Err("Token has no session".to_string()),
+            };
+        };
+        let now = chrono::Utc::now();
+
+        let session: Option<(i32, chrono::DateTime<chrono::Utc>)> = sqlx::query_as(
+            r#"
+            SELECT id, last_seen_at
+            FROM sessions
+            WHERE jti = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(jti)
+        .bind(claims.sub)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        let (session_id, last_seen_at) = session.ok_or("Session revoked")?;
+
+        if now - last_seen_at >= chrono::Duration::minutes(SESSION_LAST_SEEN_INTERVAL_MINUTES) {
+            sqlx::query("UPDATE sessions SET last_seen_at = $1 WHERE id = $2")
+                .bind(now)
+                .bind(session_id)
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
     pub fn validate_token(token: &str) -> Result<Claims, String> {
-        decode::<Claims>(&token, &KEYS.decoding, &Validation::default())
-            .map(|data| data.claims)
-            .map_err(|e| e.to_string())
+        KEYS.decode::<Claims>(token).map_err(|e| e.to_string())
+    }
+
+    pub fn is_personal_access_token(token: &str) -> bool {
+        token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX)
+    }
+
+    /// Resolves a personal access token to the same `Claims` a JWT would carry
+    /// and records when it was last used.
+    pub async fn validate_personal_access_token(
+        pool: &PgPool,
+        token: &str,
+    ) -> Result<Claims, String> {
+        let now = chrono::Utc::now();
+        let (user_id, email, expires_at, scopes, role): (
+            i32,
+            String,
+            Option<chrono::DateTime<chrono::Utc>>,
+            Vec<Scope>,
+            Role,
+        ) = sqlx::query_as(
+            r#"
+                UPDATE personal_access_tokens AS t
+                SET last_used_at = $2
+                FROM users AS u
+                WHERE t.token_hash = $1
+                    AND t.user_id = u.id
+                    AND (t.expires_at IS NULL OR t.expires_at > $2)
+                    AND u.locked_at IS NULL
+                RETURNING t.user_id, u.email, t.expires_at, t.scopes, u.role
+                "#,
+        )
+        .bind(Self::hash_token(token))
+        .bind(now)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Invalid or expired token".to_string())?;
+
+        Ok(Claims {
+            sub: user_id,
+            email,
+            iss: None,
+            aud: None,
+            iat: None,
+            nbf: None,
+            exp: expires_at.map_or(usize::MAX, |exp| exp.timestamp() as usize),
+            jti: None,
+            scopes,
+            role,
+            act: None,
+            personal_access_token: true,
+        })
+    }
+
+    pub fn generate_personal_access_token() -> String {
+        let secret: String = rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(40)
+            .map(char::from)
+            .collect();
+        format!("{}{}", PERSONAL_ACCESS_TOKEN_PREFIX, secret)
     }
 
     pub fn generate_verification_token() -> String {
@@ -148,41 +630,235 @@ impl AuthService {
 
     pub async fn send_verification_email(email: &str, token: &str) -> Result<(), String> {
         let verification_link = format!("http://localhost:3000/verify?token={}", token);
-
-        let email = lettre::Message::builder()
-            .from("noreply@yourapp.com".parse().unwrap())
-            .to(email.parse().unwrap())
-            .subject("Verify your email")
-            .body(format!(
+        mailer::send_email(
+            email,
+            "Verify your email",
+            format!(
                 "Click on the link to verify your email: {}",
                 verification_link
-            ))
-            .unwrap();
+            ),
+        )
+        .await
+    }
+
+    pub async fn send_already_registered_email(email: &str) -> Result<(), String> {
+        mailer::send_email(
+            email,
+            "You already have an account",
+            "Someone tried to register a new account with this email address. \
+             If it was you, sign in with your existing password instead. \
+             If it wasn't, you can safely ignore this email."
+                .to_string(),
+        )
+        .await
+    }
+
+    /// Emails a single-use sign-in link to a verified user. Unknown, unverified
+    /// and rate-limited emails are skipped silently so the caller always sees
+    /// the same response.
+    pub async fn send_magic_link(pool: &PgPool, email: &str) -> Result<(), String> {
+        let Ok(user) = Self::find_user_by_email(pool, email).await else {
+            return Ok(());
+        };
+        if !user.verified {
+            return Ok(());
+        }
+
+        let now = chrono::Utc::now();
+        let recent = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM magic_link_tokens
+            WHERE user_id = $1 AND created_at > $2
+            "#,
+            user.id,
+            now - chrono::Duration::minutes(MAGIC_LINK_TTL_MINUTES)
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if recent.count >= MAGIC_LINK_MAX_REQUESTS {
+            tracing::warn!("Magic link rate limit reached for user {}", user.id);
+            return Ok(());
+        }
 
-        // let creds = Credentials::new("smtp_username".to_string(), "smtp_password".to_string());
+        let token = Self::generate_verification_token();
+        let expires_at = now + chrono::Duration::minutes(MAGIC_LINK_TTL_MINUTES);
+        sqlx::query!(
+            r#"
+            INSERT INTO magic_link_tokens (user_id, token_hash, expires_at, created_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            user.id,
+            Self::hash_token(&token),
+            expires_at,
+            now
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        let timezone = sqlx::query_scalar!("SELECT timezone FROM users WHERE id = $1", user.id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| e.to_string())?;
 
-        // let mailer = lettre::SmtpTransport::relay("smtp.yourapp.com")
-        //     .unwrap()
-        //     .credentials(creds)
-        //     .build();
+        let magic_link = format!("http://localhost:3000/login/magic?token={}", token);
+        mailer::send_email(
+            &user.email,
+            "Your sign-in link",
+            format!(
+                "Click on the link to sign in. It works once, until {}: {}",
+                time::format_local(expires_at, &timezone),
+                magic_link
+            ),
+        )
+        .await
+    }
 
-        // local mailer for testing
-        let mailer = StubTransport::new_ok();
+    pub async fn consume_magic_link(pool: &PgPool, token: &str) -> Result<User, AuthError> {
+        let now = chrono::Utc::now();
+        let consumed = sqlx::query!(
+            r#"
+            UPDATE magic_link_tokens
+            SET used_at = $2
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > $2
+            RETURNING user_id
+            "#,
+            Self::hash_token(token),
+            now
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?
+        .ok_or(AuthError::InvalidToken)?;
 
-        mailer
-            .send(&email)
-            .map_err(|e| format!("Failed to send email: {}", e))?;
+        Self::find_user_by_id(pool, consumed.user_id)
+            .await
+            .map_err(|_| AuthError::InternalServerError)
+    }
 
-        // local mailer for testing
-        let captured_email = mailer.messages();
-        tracing::info!("Email sent: {:?}", captured_email);
-        Ok(())
+    /// Emails a single-use link to choose a new password. Earlier links for the
+    /// same user stop working.
+    pub async fn send_password_reset_link(
+        pool: &PgPool,
+        user_id: i32,
+        email: &str,
+    ) -> Result<(), String> {
+        let now = chrono::Utc::now();
+        sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens
+            SET used_at = $2
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id,
+            now
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let token = Self::generate_verification_token();
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens (user_id, token_hash, expires_at, created_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            user_id,
+            Self::hash_token(&token),
+            now + chrono::Duration::hours(PASSWORD_RESET_TTL_HOURS),
+            now
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let reset_link = format!("http://localhost:3000/password/reset?token={}", token);
+        mailer::send_email(
+            email,
+            "Reset your password",
+            format!(
+                "Your password has to be changed before you can sign in with it again. \
+                 Choose a new one within {} hours: {}",
+                PASSWORD_RESET_TTL_HOURS, reset_link
+            ),
+        )
+        .await
+    }
+
+    /// Sets a new password from a reset link. The token is only used up once
+    /// the new password passes the policy, so a rejected attempt can be retried.
+    pub async fn reset_password(
+        pool: &PgPool,
+        token: &str,
+        password: &str,
+    ) -> Result<(), AuthError> {
+        let now = chrono::Utc::now();
+        let token_hash = Self::hash_token(token);
+        let reset = sqlx::query!(
+            r#"
+            SELECT t.user_id, u.email
+            FROM password_reset_tokens t
+            JOIN users u ON u.id = t.user_id
+            WHERE t.token_hash = $1 AND t.used_at IS NULL AND t.expires_at > $2
+            "#,
+            token_hash,
+            now
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?
+        .ok_or(AuthError::InvalidToken)?;
+
+        PasswordPolicy::check(password, &Self::password_user_inputs(&reset.email)).await?;
+        let password_hash = Self::hash_password(password)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+        sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens
+            SET used_at = $2
+            WHERE token_hash = $1 AND used_at IS NULL
+            RETURNING id
+            "#,
+            token_hash,
+            now
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?
+        .ok_or(AuthError::InvalidToken)?;
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password = $1, password_reset_required = FALSE
+            WHERE id = $2
+            "#,
+            password_hash,
+            reset.user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+        tx.commit()
+            .await
+            .map_err(|_| AuthError::InternalServerError)
     }
 
     pub async fn verify(pool: &PgPool, token: &str) -> Result<(), String> {
         let user = sqlx::query_as!(
                 User,
-                "SELECT id, email, password, created_at, verified as \"verified!\", verification_token FROM users WHERE verification_token = $1",
+                r#"
+                SELECT id, email, password, verified as "verified!", role as "role: Role", locked_at,
+                    password_reset_required
+                FROM users
+                WHERE verification_token = $1
+                "#,
                 token
             )
             .fetch_optional(pool)
@@ -204,4 +880,241 @@ impl AuthService {
 
         Ok(())
     }
+
+    pub async fn is_totp_enabled(pool: &PgPool, user_id: i32) -> Result<bool, String> {
+        let row = sqlx::query!(
+            r#"
+            SELECT totp_enabled
+            FROM users
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(row.totp_enabled)
+    }
+
+    pub fn create_mfa_challenge(
+        user: &User,
+        mfa_methods: Vec<MfaMethod>,
+    ) -> Result<MfaChallenge, String> {
+        let exp = (chrono::Utc::now() + chrono::Duration::minutes(MFA_TOKEN_TTL_MINUTES))
+            .timestamp() as usize;
+        let claims = MfaClaims {
+            sub: user.id,
+            iss: KEYS.issuer.clone(),
+            aud: KEYS.audience.clone(),
+            mfa_pending: true,
+            exp,
+        };
+        let token = KEYS.encode(&claims).map_err(|e| e.to_string())?;
+        Ok(MfaChallenge::new(token, mfa_methods))
+    }
+
+    pub fn validate_mfa_token(token: &str) -> Result<MfaClaims, AuthError> {
+        KEYS.decode::<MfaClaims>(token)
+            .ok()
+            .filter(|claims| claims.mfa_pending)
+            .ok_or(AuthError::InvalidToken)
+    }
+
+    pub async fn start_totp_enrollment(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<TotpEnrollmentResponse, AuthError> {
+        let user = sqlx::query!(
+            r#"
+            SELECT email, totp_enabled
+            FROM users
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        if user.totp_enabled {
+            return Err(AuthError::MfaAlreadyEnabled);
+        }
+
+        let secret = totp::generate_secret();
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_secret = $1, totp_last_step = NULL
+            WHERE id = $2
+            "#,
+            secret,
+            user_id
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        let otpauth_uri = totp::otpauth_uri(&secret, &user.email);
+        Ok(TotpEnrollmentResponse {
+            secret,
+            otpauth_uri,
+        })
+    }
+
+    pub async fn confirm_totp_enrollment(
+        pool: &PgPool,
+        user_id: i32,
+        code: &str,
+    ) -> Result<Vec<String>, AuthError> {
+        let user = sqlx::query!(
+            r#"
+            SELECT totp_secret, totp_enabled
+            FROM users
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        if user.totp_enabled {
+            return Err(AuthError::MfaAlreadyEnabled);
+        }
+        let secret = user.totp_secret.ok_or(AuthError::MfaNotEnrolled)?;
+        let step =
+            totp::verify(&secret, code, Self::unix_now()).ok_or(AuthError::InvalidMfaCode)?;
+
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| Self::generate_recovery_code())
+            .collect();
+        let code_hashes: Vec<String> = recovery_codes
+            .iter()
+            .map(|code| Self::hash_token(code))
+            .collect();
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_enabled = TRUE, totp_last_step = $1
+            WHERE id = $2
+            "#,
+            step as i64,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash, created_at)
+            SELECT $1, code_hash, $3
+            FROM UNNEST($2::TEXT[]) AS code_hash
+            "#,
+            user_id,
+            &code_hashes,
+            chrono::Utc::now()
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        tx.commit()
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        Ok(recovery_codes)
+    }
+
+    /// Accepts either a current TOTP code or an unused recovery code.
+    pub async fn verify_second_factor(
+        pool: &PgPool,
+        user_id: i32,
+        code: &str,
+    ) -> Result<(), AuthError> {
+        let code = code.trim();
+        let user = sqlx::query!(
+            r#"
+            SELECT totp_secret, totp_enabled, totp_last_step
+            FROM users
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        let secret = match (user.totp_enabled, user.totp_secret) {
+            (true, Some(secret)) => secret,
+            _ => return Err(AuthError::MfaNotEnrolled),
+        };
+
+        if let Some(step) = totp::verify(&secret, code, Self::unix_now()) {
+            // The conditional update rejects a code whose time step was already used.
+            let updated = sqlx::query!(
+                r#"
+                UPDATE users
+                SET totp_last_step = $1
+                WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)
+                "#,
+                step as i64,
+                user_id
+            )
+            .execute(pool)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+            return match updated.rows_affected() {
+                0 => Err(AuthError::InvalidMfaCode),
+                _ => Ok(()),
+            };
+        }
+
+        let used = sqlx::query!(
+            r#"
+            UPDATE recovery_codes
+            SET used_at = $1
+            WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL
+            "#,
+            chrono::Utc::now(),
+            user_id,
+            Self::hash_token(&code.to_ascii_lowercase())
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        match used.rows_affected() {
+            0 => Err(AuthError::InvalidMfaCode),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn generate_recovery_code() -> String {
+        rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(10)
+            .map(|c| char::from(c).to_ascii_lowercase())
+            .collect()
+    }
+
+    /// SHA-256 hex digest for high-entropy secrets such as recovery codes.
+    pub fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    fn unix_now() -> u64 {
+        chrono::Utc::now().timestamp() as u64
+    }
 }
@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+
+use crate::{config::login_throttle_config::LoginThrottleConfig, shared::mailer};
+
+use super::models::AuthError;
+
+pub static LOGIN_THROTTLE_CONFIG: LazyLock<LoginThrottleConfig> =
+    LazyLock::new(LoginThrottleConfig::from_env);
+
+/// Fast path: keys known to be blocked are rejected without a database round trip.
+static BLOCKED_UNTIL: LazyLock<Mutex<HashMap<String, DateTime<Utc>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub struct LoginThrottle;
+
+impl LoginThrottle {
+    pub async fn check(pool: &PgPool, email: &str, ip: &str) -> Result<(), AuthError> {
+        Self::check_keys(pool, &Self::keys(email, ip)).await
+    }
+
+    pub async fn record_failure(pool: &PgPool, email: &str, ip: &str) -> Result<(), AuthError> {
+        Self::record_failure_for(pool, Self::keys(email, ip), email).await
+    }
+
+    /// Clears the account's failures only. The IP counter ages out on its
+    /// own, or signing in to an account of one's own would reset the limit
+    /// on guesses against everyone else's.
+    pub async fn record_success(pool: &PgPool, email: &str) -> Result<(), AuthError> {
+        Self::clear(pool, Self::account_key(email)).await
+    }
+
+    /// Second-factor attempts are counted per user, apart from password
+    /// failures, so a correct password doesn't reset them.
+    pub async fn check_second_factor(
+        pool: &PgPool,
+        user_id: i32,
+        ip: &str,
+    ) -> Result<(), AuthError> {
+        Self::check_keys(pool, &Self::second_factor_keys(user_id, ip)).await
+    }
+
+    pub async fn record_second_factor_failure(
+        pool: &PgPool,
+        user_id: i32,
+        email: &str,
+        ip: &str,
+    ) -> Result<(), AuthError> {
+        Self::record_failure_for(pool, Self::second_factor_keys(user_id, ip), email).await
+    }
+
+    /// Like `record_success`, leaves the IP counter alone.
+    pub async fn record_second_factor_success(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<(), AuthError> {
+        Self::clear(pool, Self::second_factor_key(user_id)).await
+    }
+
+    async fn check_keys(pool: &PgPool, keys: &[String; 2]) -> Result<(), AuthError> {
+        let now = Utc::now();
+
+        let cached = {
+            let blocked = BLOCKED_UNTIL.lock().unwrap();
+            keys.iter()
+                .filter_map(|key| blocked.get(key))
+                .max()
+                .copied()
+        };
+        if let Some(until) = cached.filter(|until| *until > now) {
+            return Err(Self::too_many_attempts(until, now));
+        }
+
+        let blocked: Option<(String, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT attempt_key, blocked_until
+            FROM login_attempts
+            WHERE attempt_key = ANY($1) AND blocked_until > $2
+            ORDER BY blocked_until DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(keys)
+        .bind(now)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        match blocked {
+            Some((key, until)) => {
+                Self::cache(key, until);
+                Err(Self::too_many_attempts(until, now))
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn record_failure_for(
+        pool: &PgPool,
+        [account_key, ip_key]: [String; 2],
+        email: &str,
+    ) -> Result<(), AuthError> {
+        let config = &*LOGIN_THROTTLE_CONFIG;
+
+        let account_failures = Self::increment(pool, &account_key).await?;
+        let ip_failures = Self::increment(pool, &ip_key).await?;
+
+        Self::block(pool, &account_key, account_failures, config.max_failures).await?;
+        Self::block(pool, &ip_key, ip_failures, config.ip_max_failures).await?;
+
+        if account_failures == config.max_failures {
+            Self::send_lockout_email(pool, email).await;
+        }
+        Ok(())
+    }
+
+    async fn clear(pool: &PgPool, key: String) -> Result<(), AuthError> {
+        sqlx::query("DELETE FROM login_attempts WHERE attempt_key = $1")
+            .bind(&key)
+            .execute(pool)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        BLOCKED_UNTIL.lock().unwrap().remove(&key);
+        Ok(())
+    }
+
+    /// Delay before the next attempt after `failures` consecutive failures.
+    pub fn backoff(config: &LoginThrottleConfig, failures: i32, max_failures: i32) -> Duration {
+        if failures >= max_failures {
+            return Duration::minutes(config.lockout_minutes);
+        }
+        let exponent = (failures - 1).clamp(0, 30) as u32;
+        let seconds = config
+            .backoff_base_seconds
+            .saturating_mul(2i64.saturating_pow(exponent))
+            .min(config.backoff_max_seconds);
+        Duration::seconds(seconds)
+    }
+
+    async fn increment(pool: &PgPool, key: &str) -> Result<i32, AuthError> {
+        let now = Utc::now();
+        // Failures older than the lockout window no longer count.
+        let window_start = now - Duration::minutes(LOGIN_THROTTLE_CONFIG.lockout_minutes);
+        let (failures,): (i32,) = sqlx::query_as(
+            r#"
+            INSERT INTO login_attempts (attempt_key, failures, last_failure_at)
+            VALUES ($1, 1, $2)
+            ON CONFLICT (attempt_key) DO UPDATE
+            SET failures = CASE
+                    WHEN login_attempts.last_failure_at < $3 THEN 1
+                    ELSE login_attempts.failures + 1
+                END,
+                last_failure_at = $2
+            RETURNING failures
+            "#,
+        )
+        .bind(key)
+        .bind(now)
+        .bind(window_start)
+        .fetch_one(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+        Ok(failures)
+    }
+
+    async fn block(
+        pool: &PgPool,
+        key: &str,
+        failures: i32,
+        max_failures: i32,
+    ) -> Result<(), AuthError> {
+        let until = Utc::now() + Self::backoff(&LOGIN_THROTTLE_CONFIG, failures, max_failures);
+        sqlx::query("UPDATE login_attempts SET blocked_until = $1 WHERE attempt_key = $2")
+            .bind(until)
+            .bind(key)
+            .execute(pool)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+        Self::cache(key.to_string(), until);
+        Ok(())
+    }
+
+    async fn send_lockout_email(pool: &PgPool, email: &str) {
+        let exists = sqlx::query("SELECT id FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(pool)
+            .await
+            .is_ok_and(|row| row.is_some());
+        if !exists {
+            return;
+        }
+
+        let body = format!(
+            "Your account was locked for {} minutes after {} failed sign-in attempts. \
+             If this wasn't you, consider changing your password.",
+            LOGIN_THROTTLE_CONFIG.lockout_minutes, LOGIN_THROTTLE_CONFIG.max_failures
+        );
+        if let Err(err) = mailer::send_email(email, "Your account has been locked", body).await {
+            tracing::error!("Failed to send lockout email: {}", err);
+        }
+    }
+
+    fn cache(key: String, until: DateTime<Utc>) {
+        let now = Utc::now();
+        let mut blocked = BLOCKED_UNTIL.lock().unwrap();
+        blocked.retain(|_, until| *until > now);
+        blocked.insert(key, until);
+    }
+
+    fn keys(email: &str, ip: &str) -> [String; 2] {
+        [Self::account_key(email), Self::ip_key(ip)]
+    }
+
+    fn second_factor_keys(user_id: i32, ip: &str) -> [String; 2] {
+        [Self::second_factor_key(user_id), Self::ip_key(ip)]
+    }
+
+    fn account_key(email: &str) -> String {
+        format!("account:{}", email.trim().to_lowercase())
+    }
+
+    fn second_factor_key(user_id: i32) -> String {
+        format!("mfa:{}", user_id)
+    }
+
+    fn ip_key(ip: &str) -> String {
+        format!("ip:{}", ip)
+    }
+
+    fn too_many_attempts(until: DateTime<Utc>, now: DateTime<Utc>) -> AuthError {
+        let retry_after = (until - now).num_seconds().max(1);
+        AuthError::TooManyAttempts(retry_after)
+    }
+}
@@ -0,0 +1,126 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+pub const ISSUER: &str = "Todo.rs";
+pub const STEP_SECONDS: u64 = 30;
+pub const DIGITS: u32 = 6;
+/// Number of time steps accepted either side of the current one.
+pub const SKEW_STEPS: u64 = 1;
+
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; 20];
+    rand::rng().fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        issuer = urlencoding::encode(ISSUER),
+        account = urlencoding::encode(account),
+    )
+}
+
+pub fn time_step(unix_time: u64) -> u64 {
+    unix_time / STEP_SECONDS
+}
+
+/// HOTP value (RFC 4226) for the given counter.
+pub fn hotp(key: &[u8], counter: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+/// Checks `code` against the secret at `unix_time` and returns the matching
+/// time step, so callers can reject a code that was already used.
+pub fn verify(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let current = time_step(unix_time);
+    (current.saturating_sub(SKEW_STEPS)..=current + SKEW_STEPS)
+        .find(|step| hotp(&key, *step) == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The RFC 6238 SHA1 key, "12345678901234567890", in base32.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    /// RFC 6238 Appendix B SHA1 vectors. The RFC lists 8-digit codes; with
+    /// six digits they keep their last six.
+    const RFC_VECTORS: [(u64, &str); 6] = [
+        (59, "287082"),
+        (1111111109, "081804"),
+        (1111111111, "050471"),
+        (1234567890, "005924"),
+        (2000000000, "279037"),
+        (20000000000, "353130"),
+    ];
+
+    #[test]
+    fn hotp_matches_rfc_6238_vectors() {
+        let key = BASE32_NOPAD.decode(RFC_SECRET.as_bytes()).unwrap();
+        for (unix_time, code) in RFC_VECTORS {
+            assert_eq!(hotp(&key, time_step(unix_time)), code, "T = {}", unix_time);
+        }
+    }
+
+    #[test]
+    fn verify_returns_matching_step() {
+        for (unix_time, code) in RFC_VECTORS {
+            assert_eq!(
+                verify(RFC_SECRET, code, unix_time),
+                Some(time_step(unix_time))
+            );
+        }
+    }
+
+    #[test]
+    fn verify_accepts_one_step_either_side() {
+        // 1111111109 is step 37037036; its code stays valid for the step
+        // before and after, and no further.
+        let (unix_time, code) = RFC_VECTORS[1];
+        let step = time_step(unix_time);
+        let step_start = step * STEP_SECONDS;
+
+        assert_eq!(verify(RFC_SECRET, code, step_start - 1), Some(step));
+        assert_eq!(
+            verify(RFC_SECRET, code, step_start + 2 * STEP_SECONDS - 1),
+            Some(step)
+        );
+        assert_eq!(
+            verify(RFC_SECRET, code, step_start - STEP_SECONDS - 1),
+            None
+        );
+        assert_eq!(
+            verify(RFC_SECRET, code, step_start + 2 * STEP_SECONDS),
+            None
+        );
+    }
+
+    #[test]
+    fn verify_rejects_wrong_code() {
+        let (unix_time, code) = RFC_VECTORS[0];
+        let wrong = if code == "000000" { "000001" } else { "000000" };
+        assert_eq!(verify(RFC_SECRET, wrong, unix_time), None);
+        assert_eq!(verify(RFC_SECRET, "28708", unix_time), None);
+        assert_eq!(verify("not base32!", code, unix_time), None);
+    }
+}
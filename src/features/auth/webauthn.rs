@@ -0,0 +1,844 @@
+use std::sync::LazyLock;
+
+use ciborium::Value;
+use data_encoding::BASE64URL_NOPAD;
+use rand::RngCore;
+use ring::signature::{
+    RsaPublicKeyComponents, UnparsedPublicKey, ECDSA_P256_SHA256_ASN1, ED25519,
+    RSA_PKCS1_2048_8192_SHA256,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::config::webauthn_config::WebauthnConfig;
+
+use super::models::{
+    AuthError, AuthenticatorSelection, CredentialDescriptor, Passkey, PasskeyAssertionDto,
+    PubKeyCredParam, PublicKeyCredentialCreationOptions, PublicKeyCredentialRequestOptions,
+    RegisterPasskeyDto, RelyingParty, WebauthnUser,
+};
+
+pub static WEBAUTHN_CONFIG: LazyLock<WebauthnConfig> = LazyLock::new(WebauthnConfig::from_env);
+
+const CHALLENGE_TTL_MINUTES: i64 = 5;
+const TIMEOUT_MILLISECONDS: u64 = 60_000;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+const COSE_ALG_ES256: i64 = -7;
+const COSE_ALG_EDDSA: i64 = -8;
+const COSE_ALG_RS256: i64 = -257;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Ceremony {
+    Registration,
+    Authentication,
+}
+
+impl Ceremony {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Ceremony::Registration => "registration",
+            Ceremony::Authentication => "authentication",
+        }
+    }
+
+    fn client_data_type(&self) -> &'static str {
+        match self {
+            Ceremony::Registration => "webauthn.create",
+            Ceremony::Authentication => "webauthn.get",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClientData {
+    #[serde(rename = "type")]
+    pub ceremony_type: String,
+    pub challenge: String,
+    pub origin: String,
+}
+
+#[derive(Debug)]
+pub struct AuthenticatorData {
+    pub rp_id_hash: Vec<u8>,
+    pub flags: u8,
+    pub sign_count: u32,
+    pub attested_credential: Option<AttestedCredential>,
+}
+
+#[derive(Debug)]
+pub struct AttestedCredential {
+    pub credential_id: Vec<u8>,
+    /// COSE_Key, re-encoded as CBOR.
+    pub public_key: Vec<u8>,
+}
+
+/// Checks `clientDataJSON` for the ceremony type and origin and returns it so
+/// the caller can look up the challenge.
+pub fn parse_client_data(
+    config: &WebauthnConfig,
+    client_data_json: &[u8],
+    ceremony: Ceremony,
+) -> Result<ClientData, String> {
+    let client_data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|e| e.to_string())?;
+    if client_data.ceremony_type != ceremony.client_data_type() {
+        return Err(format!("Unexpected type {}", client_data.ceremony_type));
+    }
+    if client_data.origin != config.origin {
+        return Err(format!("Unexpected origin {}", client_data.origin));
+    }
+    Ok(client_data)
+}
+
+pub fn parse_authenticator_data(data: &[u8]) -> Result<AuthenticatorData, String> {
+    if data.len() < 37 {
+        return Err("Authenticator data is too short".to_string());
+    }
+    let rp_id_hash = data[..32].to_vec();
+    let flags = data[32];
+    let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+    let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+        // AAGUID (16 bytes), credential ID length (2 bytes), credential ID, COSE key.
+        let rest = data
+            .get(37 + 16..)
+            .ok_or("Missing attested credential data")?;
+        let id_len = u16::from_be_bytes([
+            *rest.first().ok_or("Missing credential ID length")?,
+            *rest.get(1).ok_or("Missing credential ID length")?,
+        ]) as usize;
+        let credential_id = rest
+            .get(2..2 + id_len)
+            .ok_or("Credential ID is truncated")?
+            .to_vec();
+
+        let mut cose_key = &rest[2 + id_len..];
+        let key: Value = ciborium::de::from_reader(&mut cose_key).map_err(|e| e.to_string())?;
+        let mut public_key = Vec::new();
+        ciborium::ser::into_writer(&key, &mut public_key).map_err(|e| e.to_string())?;
+
+        Some(AttestedCredential {
+            credential_id,
+            public_key,
+        })
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData {
+        rp_id_hash,
+        flags,
+        sign_count,
+        attested_credential,
+    })
+}
+
+/// Parses an attestation object. We request attestation "none", so the
+/// attestation statement is not verified whatever its format.
+pub fn parse_attestation_object(attestation_object: &[u8]) -> Result<AuthenticatorData, String> {
+    let value: Value = ciborium::de::from_reader(attestation_object).map_err(|e| e.to_string())?;
+    let auth_data = map_get(&value, &Value::Text("authData".to_string()))
+        .and_then(Value::as_bytes)
+        .ok_or("Attestation object has no authData")?;
+    parse_authenticator_data(auth_data)
+}
+
+/// Verifies `signature` over `authenticatorData || SHA-256(clientDataJSON)`
+/// with a COSE public key (ES256, EdDSA or RS256).
+pub fn verify_signature(cose_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), String> {
+    let key: Value = ciborium::de::from_reader(cose_key).map_err(|e| e.to_string())?;
+    let alg = cose_int(&key, 3).ok_or("COSE key has no algorithm")?;
+
+    let verified = match alg {
+        COSE_ALG_ES256 => {
+            let x = cose_bytes(&key, -2).ok_or("EC2 key has no x")?;
+            let y = cose_bytes(&key, -3).ok_or("EC2 key has no y")?;
+            let point = [&[0x04], x, y].concat();
+            UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, point).verify(message, signature)
+        }
+        COSE_ALG_EDDSA => {
+            let x = cose_bytes(&key, -2).ok_or("OKP key has no x")?;
+            UnparsedPublicKey::new(&ED25519, x).verify(message, signature)
+        }
+        COSE_ALG_RS256 => {
+            let n = cose_bytes(&key, -1).ok_or("RSA key has no n")?;
+            let e = cose_bytes(&key, -2).ok_or("RSA key has no e")?;
+            RsaPublicKeyComponents { n, e }.verify(&RSA_PKCS1_2048_8192_SHA256, message, signature)
+        }
+        other => return Err(format!("Unsupported COSE algorithm {}", other)),
+    };
+    verified.map_err(|_| "Invalid signature".to_string())
+}
+
+pub fn verify_authenticator_flags(
+    config: &WebauthnConfig,
+    authenticator_data: &AuthenticatorData,
+    require_user_verification: bool,
+) -> Result<(), String> {
+    if authenticator_data.rp_id_hash != Sha256::digest(config.rp_id.as_bytes()).as_slice() {
+        return Err("RP ID hash mismatch".to_string());
+    }
+    if authenticator_data.flags & FLAG_USER_PRESENT == 0 {
+        return Err("User not present".to_string());
+    }
+    if require_user_verification && authenticator_data.flags & FLAG_USER_VERIFIED == 0 {
+        return Err("User not verified".to_string());
+    }
+    Ok(())
+}
+
+/// The bytes of an assertion response, base64url-decoded.
+pub struct AssertionResponse<'a> {
+    pub client_data_json: &'a [u8],
+    pub authenticator_data: &'a [u8],
+    pub signature: &'a [u8],
+}
+
+/// Everything in registration (WebAuthn §7.1) that doesn't need storage:
+/// the client data must answer `challenge`, and the authenticator data must be
+/// for our RP with the user present. Returns the credential and its counter.
+pub fn verify_registration(
+    config: &WebauthnConfig,
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+    challenge: &str,
+) -> Result<(AttestedCredential, u32), String> {
+    let client_data = parse_client_data(config, client_data_json, Ceremony::Registration)?;
+    if client_data.challenge.trim_end_matches('=') != challenge.trim_end_matches('=') {
+        return Err("Challenge mismatch".to_string());
+    }
+
+    let authenticator_data = parse_attestation_object(attestation_object)?;
+    verify_authenticator_flags(config, &authenticator_data, false)?;
+    let credential = authenticator_data
+        .attested_credential
+        .ok_or("No attested credential data")?;
+    Ok((credential, authenticator_data.sign_count))
+}
+
+/// Everything in authentication (WebAuthn §7.2) that doesn't need storage,
+/// given the credential's stored public key and counter. Returns the new
+/// counter to store.
+pub fn verify_assertion(
+    config: &WebauthnConfig,
+    assertion: &AssertionResponse,
+    challenge: &str,
+    public_key: &[u8],
+    stored_sign_count: i64,
+    require_user_verification: bool,
+) -> Result<i64, String> {
+    let client_data =
+        parse_client_data(config, assertion.client_data_json, Ceremony::Authentication)?;
+    if client_data.challenge.trim_end_matches('=') != challenge.trim_end_matches('=') {
+        return Err("Challenge mismatch".to_string());
+    }
+
+    let authenticator_data = parse_authenticator_data(assertion.authenticator_data)?;
+    verify_authenticator_flags(config, &authenticator_data, require_user_verification)?;
+
+    let message = [
+        assertion.authenticator_data,
+        Sha256::digest(assertion.client_data_json).as_slice(),
+    ]
+    .concat();
+    verify_signature(public_key, &message, assertion.signature)?;
+
+    // A counter that does not increase suggests a cloned authenticator.
+    // Authenticators that don't count always report 0.
+    let sign_count = authenticator_data.sign_count as i64;
+    if sign_count != 0 && sign_count <= stored_sign_count {
+        return Err("Signature counter did not increase".to_string());
+    }
+    Ok(sign_count)
+}
+
+fn map_get<'a>(value: &'a Value, key: &Value) -> Option<&'a Value> {
+    value
+        .as_map()?
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v)
+}
+
+fn cose_int(key: &Value, label: i64) -> Option<i64> {
+    map_get(key, &Value::from(label))?
+        .as_integer()
+        .and_then(|value| i64::try_from(value).ok())
+}
+
+fn cose_bytes(key: &Value, label: i64) -> Option<&[u8]> {
+    map_get(key, &Value::from(label))?
+        .as_bytes()
+        .map(Vec::as_slice)
+}
+
+fn decode_base64url(value: &str) -> Result<Vec<u8>, AuthError> {
+    BASE64URL_NOPAD
+        .decode(value.trim_end_matches('=').as_bytes())
+        .map_err(|_| AuthError::InvalidWebauthnResponse)
+}
+
+fn rejected(reason: impl std::fmt::Display) -> AuthError {
+    tracing::warn!("WebAuthn: {}", reason);
+    AuthError::InvalidWebauthnResponse
+}
+
+pub struct WebauthnService;
+
+impl WebauthnService {
+    pub async fn registration_options(
+        pool: &PgPool,
+        user_id: i32,
+        email: &str,
+    ) -> Result<PublicKeyCredentialCreationOptions, AuthError> {
+        let challenge = Self::create_challenge(pool, Some(user_id), Ceremony::Registration).await?;
+        let exclude_credentials = Self::credential_descriptors(pool, user_id).await?;
+        let config = &*WEBAUTHN_CONFIG;
+
+        Ok(PublicKeyCredentialCreationOptions {
+            challenge,
+            rp: RelyingParty {
+                id: config.rp_id.clone(),
+                name: config.rp_name.clone(),
+            },
+            user: WebauthnUser {
+                id: BASE64URL_NOPAD.encode(user_id.to_string().as_bytes()),
+                name: email.to_string(),
+                display_name: email.to_string(),
+            },
+            pub_key_cred_params: [COSE_ALG_ES256, COSE_ALG_EDDSA, COSE_ALG_RS256]
+                .into_iter()
+                .map(|alg| PubKeyCredParam {
+                    credential_type: "public-key".to_string(),
+                    alg,
+                })
+                .collect(),
+            timeout: TIMEOUT_MILLISECONDS,
+            attestation: "none".to_string(),
+            exclude_credentials,
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: "preferred".to_string(),
+                user_verification: "preferred".to_string(),
+            },
+        })
+    }
+
+    pub async fn register(
+        pool: &PgPool,
+        user_id: i32,
+        register_passkey_dto: RegisterPasskeyDto,
+    ) -> Result<Passkey, AuthError> {
+        let config = &*WEBAUTHN_CONFIG;
+        let client_data_json = decode_base64url(&register_passkey_dto.response.client_data_json)?;
+        let attestation_object =
+            decode_base64url(&register_passkey_dto.response.attestation_object)?;
+
+        let client_data = parse_client_data(config, &client_data_json, Ceremony::Registration)
+            .map_err(rejected)?;
+        let challenge_user =
+            Self::consume_challenge(pool, &client_data.challenge, Ceremony::Registration).await?;
+        if challenge_user != Some(user_id) {
+            return Err(rejected("Challenge was issued to another user"));
+        }
+
+        let (credential, sign_count) = verify_registration(
+            config,
+            &client_data_json,
+            &attestation_object,
+            &client_data.challenge,
+        )
+        .map_err(rejected)?;
+        let credential_id = BASE64URL_NOPAD.encode(&credential.credential_id);
+        if credential_id != register_passkey_dto.id.trim_end_matches('=') {
+            return Err(rejected(
+                "Credential ID does not match the attested credential",
+            ));
+        }
+
+        let name = register_passkey_dto
+            .name
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| "Passkey".to_string());
+
+        sqlx::query_as(
+            r#"
+            INSERT INTO webauthn_credentials (user_id, credential_id, public_key, sign_count, name, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, name, created_at, last_used_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(credential_id)
+        .bind(credential.public_key)
+        .bind(sign_count as i64)
+        .bind(name.trim())
+        .bind(chrono::Utc::now())
+        .fetch_one(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)
+    }
+
+    /// Options for an assertion. Without a user the browser offers any
+    /// discoverable passkey for this RP, so nothing about accounts leaks.
+    pub async fn authentication_options(
+        pool: &PgPool,
+        user_id: Option<i32>,
+        require_user_verification: bool,
+    ) -> Result<PublicKeyCredentialRequestOptions, AuthError> {
+        let challenge = Self::create_challenge(pool, user_id, Ceremony::Authentication).await?;
+        let allow_credentials = match user_id {
+            Some(user_id) => Self::credential_descriptors(pool, user_id).await?,
+            None => Vec::new(),
+        };
+
+        Ok(PublicKeyCredentialRequestOptions {
+            challenge,
+            rp_id: WEBAUTHN_CONFIG.rp_id.clone(),
+            timeout: TIMEOUT_MILLISECONDS,
+            allow_credentials,
+            user_verification: match require_user_verification {
+                true => "required",
+                false => "preferred",
+            }
+            .to_string(),
+        })
+    }
+
+    /// Verifies an assertion and returns the id of the user who owns the
+    /// credential. `expected_user` pins the ceremony to one user (second factor).
+    pub async fn authenticate(
+        pool: &PgPool,
+        assertion: &PasskeyAssertionDto,
+        expected_user: Option<i32>,
+        require_user_verification: bool,
+    ) -> Result<i32, AuthError> {
+        let config = &*WEBAUTHN_CONFIG;
+        let client_data_json = decode_base64url(&assertion.response.client_data_json)?;
+        let authenticator_data = decode_base64url(&assertion.response.authenticator_data)?;
+        let signature = decode_base64url(&assertion.response.signature)?;
+
+        let client_data = parse_client_data(config, &client_data_json, Ceremony::Authentication)
+            .map_err(rejected)?;
+        let challenge_user =
+            Self::consume_challenge(pool, &client_data.challenge, Ceremony::Authentication).await?;
+        if challenge_user != expected_user {
+            return Err(rejected("Challenge was issued for another login"));
+        }
+
+        let credential: Option<(i32, i32, Vec<u8>, i64)> = sqlx::query_as(
+            r#"
+            SELECT id, user_id, public_key, sign_count
+            FROM webauthn_credentials
+            WHERE credential_id = $1
+            "#,
+        )
+        .bind(assertion.id.trim_end_matches('='))
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+        let (credential_id, user_id, public_key, stored_sign_count) =
+            credential.ok_or_else(|| rejected("Unknown credential"))?;
+        if expected_user.is_some_and(|expected| expected != user_id) {
+            return Err(rejected("Credential belongs to another user"));
+        }
+
+        let assertion_response = AssertionResponse {
+            client_data_json: &client_data_json,
+            authenticator_data: &authenticator_data,
+            signature: &signature,
+        };
+        let sign_count = verify_assertion(
+            config,
+            &assertion_response,
+            &client_data.challenge,
+            &public_key,
+            stored_sign_count,
+            require_user_verification,
+        )
+        .map_err(rejected)?;
+
+        sqlx::query(
+            r#"
+            UPDATE webauthn_credentials
+            SET sign_count = $1, last_used_at = $2
+            WHERE id = $3
+            "#,
+        )
+        .bind(sign_count)
+        .bind(chrono::Utc::now())
+        .bind(credential_id)
+        .execute(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        Ok(user_id)
+    }
+
+    pub async fn has_credentials(pool: &PgPool, user_id: i32) -> Result<bool, AuthError> {
+        let credential: Option<(i32,)> =
+            sqlx::query_as("SELECT id FROM webauthn_credentials WHERE user_id = $1 LIMIT 1")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await
+                .map_err(|_| AuthError::InternalServerError)?;
+        Ok(credential.is_some())
+    }
+
+    async fn credential_descriptors(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<Vec<CredentialDescriptor>, AuthError> {
+        let credential_ids: Vec<(String,)> =
+            sqlx::query_as("SELECT credential_id FROM webauthn_credentials WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_all(pool)
+                .await
+                .map_err(|_| AuthError::InternalServerError)?;
+
+        Ok(credential_ids
+            .into_iter()
+            .map(|(id,)| CredentialDescriptor {
+                credential_type: "public-key".to_string(),
+                id,
+            })
+            .collect())
+    }
+
+    async fn create_challenge(
+        pool: &PgPool,
+        user_id: Option<i32>,
+        ceremony: Ceremony,
+    ) -> Result<String, AuthError> {
+        let mut bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        let challenge = BASE64URL_NOPAD.encode(&bytes);
+        let now = chrono::Utc::now();
+
+        sqlx::query("DELETE FROM webauthn_challenges WHERE created_at <= $1")
+            .bind(now - chrono::Duration::minutes(CHALLENGE_TTL_MINUTES))
+            .execute(pool)
+            .await
+            .map_err(|_| AuthError::InternalServerError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO webauthn_challenges (challenge, user_id, ceremony, created_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(&challenge)
+        .bind(user_id)
+        .bind(ceremony.as_str())
+        .bind(now)
+        .execute(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        Ok(challenge)
+    }
+
+    /// Challenges are single use; returns the user the challenge was issued to.
+    async fn consume_challenge(
+        pool: &PgPool,
+        challenge: &str,
+        ceremony: Ceremony,
+    ) -> Result<Option<i32>, AuthError> {
+        let consumed: Option<(Option<i32>,)> = sqlx::query_as(
+            r#"
+            DELETE FROM webauthn_challenges
+            WHERE challenge = $1 AND ceremony = $2 AND created_at > $3
+            RETURNING user_id
+            "#,
+        )
+        .bind(challenge.trim_end_matches('='))
+        .bind(ceremony.as_str())
+        .bind(chrono::Utc::now() - chrono::Duration::minutes(CHALLENGE_TTL_MINUTES))
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AuthError::InternalServerError)?;
+
+        consumed
+            .map(|(user_id,)| user_id)
+            .ok_or_else(|| rejected("Unknown or expired challenge"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    const CHALLENGE: &str = "c2VydmVyLWlzc3VlZC1jaGFsbGVuZ2U";
+    const CREDENTIAL_ID: &[u8] = b"fixture-credential";
+
+    fn config() -> WebauthnConfig {
+        WebauthnConfig {
+            rp_id: "localhost".to_string(),
+            rp_name: "Todo.rs".to_string(),
+            origin: "http://localhost:3000".to_string(),
+        }
+    }
+
+    /// A software ES256 authenticator.
+    struct Authenticator {
+        key_pair: EcdsaKeyPair,
+        rng: SystemRandom,
+    }
+
+    impl Authenticator {
+        fn new() -> Self {
+            let rng = SystemRandom::new();
+            let pkcs8 =
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+            let key_pair =
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                    .unwrap();
+            Self { key_pair, rng }
+        }
+
+        fn cose_key(&self) -> Vec<u8> {
+            // Uncompressed point: 0x04 || x || y.
+            let point = self.key_pair.public_key().as_ref();
+            let key = Value::Map(vec![
+                (Value::from(1), Value::from(2)),
+                (Value::from(3), Value::from(COSE_ALG_ES256)),
+                (Value::from(-1), Value::from(1)),
+                (Value::from(-2), Value::Bytes(point[1..33].to_vec())),
+                (Value::from(-3), Value::Bytes(point[33..].to_vec())),
+            ]);
+            let mut cose_key = Vec::new();
+            ciborium::ser::into_writer(&key, &mut cose_key).unwrap();
+            cose_key
+        }
+
+        fn authenticator_data(
+            &self,
+            rp_id: &str,
+            flags: u8,
+            sign_count: u32,
+            attested: bool,
+        ) -> Vec<u8> {
+            let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+            data.push(flags);
+            data.extend_from_slice(&sign_count.to_be_bytes());
+            if attested {
+                data.extend_from_slice(&[0u8; 16]);
+                data.extend_from_slice(&(CREDENTIAL_ID.len() as u16).to_be_bytes());
+                data.extend_from_slice(CREDENTIAL_ID);
+                data.extend_from_slice(&self.cose_key());
+            }
+            data
+        }
+
+        fn attestation_object(&self, rp_id: &str, flags: u8) -> Vec<u8> {
+            let auth_data =
+                self.authenticator_data(rp_id, flags | FLAG_ATTESTED_CREDENTIAL_DATA, 0, true);
+            let object = Value::Map(vec![
+                (Value::from("fmt"), Value::from("none")),
+                (Value::from("attStmt"), Value::Map(Vec::new())),
+                (Value::from("authData"), Value::Bytes(auth_data)),
+            ]);
+            let mut attestation_object = Vec::new();
+            ciborium::ser::into_writer(&object, &mut attestation_object).unwrap();
+            attestation_object
+        }
+
+        fn sign(&self, authenticator_data: &[u8], client_data_json: &[u8]) -> Vec<u8> {
+            let message = [
+                authenticator_data,
+                Sha256::digest(client_data_json).as_slice(),
+            ]
+            .concat();
+            self.key_pair
+                .sign(&self.rng, &message)
+                .unwrap()
+                .as_ref()
+                .to_vec()
+        }
+    }
+
+    fn client_data(ceremony: Ceremony, challenge: &str) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "type": ceremony.client_data_type(),
+            "challenge": challenge,
+            "origin": "http://localhost:3000",
+            "crossOrigin": false,
+        }))
+        .unwrap()
+    }
+
+    /// An assertion over fresh client data and authenticator data.
+    struct Fixture {
+        client_data_json: Vec<u8>,
+        authenticator_data: Vec<u8>,
+        signature: Vec<u8>,
+    }
+
+    impl Fixture {
+        fn new(authenticator: &Authenticator, rp_id: &str, flags: u8, sign_count: u32) -> Self {
+            Self::for_challenge(authenticator, rp_id, flags, sign_count, CHALLENGE)
+        }
+
+        fn for_challenge(
+            authenticator: &Authenticator,
+            rp_id: &str,
+            flags: u8,
+            sign_count: u32,
+            challenge: &str,
+        ) -> Self {
+            let client_data_json = client_data(Ceremony::Authentication, challenge);
+            let authenticator_data =
+                authenticator.authenticator_data(rp_id, flags, sign_count, false);
+            let signature = authenticator.sign(&authenticator_data, &client_data_json);
+            Self {
+                client_data_json,
+                authenticator_data,
+                signature,
+            }
+        }
+
+        fn verify(
+            &self,
+            public_key: &[u8],
+            stored_sign_count: i64,
+            require_user_verification: bool,
+        ) -> Result<i64, String> {
+            let assertion = AssertionResponse {
+                client_data_json: &self.client_data_json,
+                authenticator_data: &self.authenticator_data,
+                signature: &self.signature,
+            };
+            verify_assertion(
+                &config(),
+                &assertion,
+                CHALLENGE,
+                public_key,
+                stored_sign_count,
+                require_user_verification,
+            )
+        }
+    }
+
+    fn register(authenticator: &Authenticator) -> AttestedCredential {
+        let (credential, sign_count) = verify_registration(
+            &config(),
+            &client_data(Ceremony::Registration, CHALLENGE),
+            &authenticator.attestation_object("localhost", FLAG_USER_PRESENT),
+            CHALLENGE,
+        )
+        .unwrap();
+        assert_eq!(sign_count, 0);
+        credential
+    }
+
+    #[test]
+    fn accepts_valid_registration() {
+        let authenticator = Authenticator::new();
+        let credential = register(&authenticator);
+
+        assert_eq!(credential.credential_id, CREDENTIAL_ID);
+        assert_eq!(credential.public_key, authenticator.cose_key());
+    }
+
+    #[test]
+    fn accepts_valid_assertion() {
+        let authenticator = Authenticator::new();
+        let credential = register(&authenticator);
+        let fixture = Fixture::new(
+            &authenticator,
+            "localhost",
+            FLAG_USER_PRESENT | FLAG_USER_VERIFIED,
+            7,
+        );
+
+        assert_eq!(fixture.verify(&credential.public_key, 6, true), Ok(7));
+    }
+
+    #[test]
+    fn rejects_wrong_rp_id_hash() {
+        let authenticator = Authenticator::new();
+        let registration = verify_registration(
+            &config(),
+            &client_data(Ceremony::Registration, CHALLENGE),
+            &authenticator.attestation_object("evil.example.com", FLAG_USER_PRESENT),
+            CHALLENGE,
+        );
+        assert!(registration.is_err());
+
+        let credential = register(&authenticator);
+        let fixture = Fixture::new(&authenticator, "evil.example.com", FLAG_USER_PRESENT, 1);
+        assert!(fixture.verify(&credential.public_key, 0, false).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_user_verification_when_required() {
+        let authenticator = Authenticator::new();
+        let credential = register(&authenticator);
+        let fixture = Fixture::new(&authenticator, "localhost", FLAG_USER_PRESENT, 1);
+
+        assert!(fixture.verify(&credential.public_key, 0, true).is_err());
+        assert_eq!(fixture.verify(&credential.public_key, 0, false), Ok(1));
+    }
+
+    #[test]
+    fn rejects_non_increasing_sign_counter() {
+        let authenticator = Authenticator::new();
+        let credential = register(&authenticator);
+        let fixture = Fixture::new(&authenticator, "localhost", FLAG_USER_PRESENT, 5);
+
+        assert!(fixture.verify(&credential.public_key, 5, false).is_err());
+        assert!(fixture.verify(&credential.public_key, 9, false).is_err());
+
+        // Authenticators without a counter always send 0.
+        let uncounted = Fixture::new(&authenticator, "localhost", FLAG_USER_PRESENT, 0);
+        assert_eq!(uncounted.verify(&credential.public_key, 0, false), Ok(0));
+    }
+
+    #[test]
+    fn rejects_bad_signature() {
+        let authenticator = Authenticator::new();
+        let credential = register(&authenticator);
+
+        let mut tampered = Fixture::new(&authenticator, "localhost", FLAG_USER_PRESENT, 1);
+        let last = tampered.signature.len() - 1;
+        tampered.signature[last] ^= 0x01;
+        assert!(tampered.verify(&credential.public_key, 0, false).is_err());
+
+        // A valid signature from another authenticator's key.
+        let other = Authenticator::new();
+        let foreign = Fixture::new(&other, "localhost", FLAG_USER_PRESENT, 1);
+        assert!(foreign.verify(&credential.public_key, 0, false).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_challenge() {
+        let authenticator = Authenticator::new();
+        let registration = verify_registration(
+            &config(),
+            &client_data(Ceremony::Registration, "b3RoZXItY2hhbGxlbmdl"),
+            &authenticator.attestation_object("localhost", FLAG_USER_PRESENT),
+            CHALLENGE,
+        );
+        assert!(registration.is_err());
+
+        let credential = register(&authenticator);
+        let fixture = Fixture::for_challenge(
+            &authenticator,
+            "localhost",
+            FLAG_USER_PRESENT,
+            1,
+            "b3RoZXItY2hhbGxlbmdl",
+        );
+        assert!(fixture.verify(&credential.public_key, 0, false).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::{
+    models::Claims,
+    scopes::{RequireScope, TasksRead, TasksWrite},
+};
+
+use super::{
+    models::{Comment, CommentDto, CommentError},
+    services::CommentService,
+};
+
+pub async fn get_comments(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Path(task_id): Path<i32>,
+) -> Result<Json<Vec<Comment>>, CommentError> {
+    let comments = CommentService::get_comments(&pool, task_id, claims.sub).await?;
+    Ok(Json(comments))
+}
+
+pub async fn create_comment(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(task_id): Path<i32>,
+    Json(comment_dto): Json<CommentDto>,
+) -> Result<Json<Comment>, CommentError> {
+    let comment =
+        CommentService::create_comment(&pool, task_id, claims.sub, &comment_dto.body).await?;
+    Ok(Json(comment))
+}
+
+pub async fn update_comment(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+    Json(comment_dto): Json<CommentDto>,
+) -> Result<Json<Comment>, CommentError> {
+    let comment = CommentService::update_comment(&pool, id, claims.sub, &comment_dto.body).await?;
+    Ok(Json(comment))
+}
+
+pub async fn delete_comment(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, CommentError> {
+    CommentService::delete_comment(&pool, id, claims.sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,57 @@
+use axum::{
+    body::Body,
+    http::{Response, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::features::tasks::models::TaskError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct Comment {
+    pub id: i32,
+    pub task_id: i32,
+    /// `None` once the author's account is deleted.
+    pub author_id: Option<i32>,
+    pub body: String,
+    /// `body` as HTML, with mentions linked to the users.
+    pub body_html: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentDto {
+    pub body: String,
+}
+
+pub enum CommentError {
+    NotFound,
+    Forbidden,
+    BadRequest,
+    InternalServerError,
+}
+
+impl From<TaskError> for CommentError {
+    fn from(err: TaskError) -> Self {
+        match err {
+            TaskError::NotFound => CommentError::NotFound,
+            TaskError::Forbidden => CommentError::Forbidden,
+            TaskError::BadRequest => CommentError::BadRequest,
+            TaskError::InternalServerError => CommentError::InternalServerError,
+        }
+    }
+}
+
+impl IntoResponse for CommentError {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            CommentError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            CommentError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            CommentError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+            CommentError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use axum::{
+    middleware,
+    routing::{get, patch},
+    Router,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::middlewares::jwt_middleware;
+
+use super::handlers;
+
+pub fn comment_routes(pool: PgPool) -> Router {
+    Router::new()
+        .route(
+            "/tasks/{id}/comments",
+            get(handlers::get_comments).post(handlers::create_comment),
+        )
+        .route(
+            "/comments/{id}",
+            patch(handlers::update_comment).delete(handlers::delete_comment),
+        )
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
+        .with_state(pool)
+}
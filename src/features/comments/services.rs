@@ -0,0 +1,230 @@
+use sqlx::PgPool;
+
+use crate::features::{
+    notifications::{
+        models::NotificationType,
+        notifier::{NewNotification, Notifier},
+    },
+    tasks::{mentions::MentionService, models::TaskPermission, services::TaskService},
+};
+
+use super::models::{Comment, CommentError};
+
+const COMMENT_MAX_LENGTH: usize = 10_000;
+
+pub struct CommentService;
+
+impl CommentService {
+    pub async fn get_comments(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+    ) -> Result<Vec<Comment>, CommentError> {
+        TaskService::require_permission(pool, task_id, user_id, TaskPermission::View).await?;
+
+        sqlx::query_as(
+            r#"
+            SELECT id, task_id, author_id, body, body_html, created_at, updated_at
+            FROM task_comments
+            WHERE task_id = $1
+            ORDER BY created_at, id
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| CommentError::InternalServerError)
+    }
+
+    /// Adds a comment and notifies the users mentioned in it.
+    pub async fn create_comment(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+        body: &str,
+    ) -> Result<Comment, CommentError> {
+        let body = Self::validate_body(body)?;
+        TaskService::require_permission(pool, task_id, user_id, TaskPermission::Edit).await?;
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| CommentError::InternalServerError)?;
+        let mentioned = MentionService::resolve(&mut tx, task_id, body).await?;
+        let comment: Comment = sqlx::query_as(
+            r#"
+            INSERT INTO task_comments (task_id, author_id, body, body_html, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, task_id, author_id, body, body_html, created_at, updated_at
+            "#,
+        )
+        .bind(task_id)
+        .bind(user_id)
+        .bind(body)
+        .bind(&mentioned.html)
+        .bind(chrono::Utc::now())
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| CommentError::InternalServerError)?;
+        let newly_mentioned = MentionService::record(
+            &mut tx,
+            task_id,
+            Some(comment.id),
+            user_id,
+            &mentioned.user_ids,
+        )
+        .await?;
+        tx.commit()
+            .await
+            .map_err(|_| CommentError::InternalServerError)?;
+
+        MentionService::notify(pool, task_id, user_id, &newly_mentioned).await;
+        Self::notify_followers(pool, task_id, user_id, &newly_mentioned).await;
+        Ok(comment)
+    }
+
+    /// Only the author can edit a comment. Users mentioned for the first time
+    /// by the edit are notified.
+    pub async fn update_comment(
+        pool: &PgPool,
+        comment_id: i32,
+        user_id: i32,
+        body: &str,
+    ) -> Result<Comment, CommentError> {
+        let body = Self::validate_body(body)?;
+        let comment = Self::get_comment(pool, comment_id, user_id).await?;
+        if comment.author_id != Some(user_id) {
+            return Err(CommentError::Forbidden);
+        }
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| CommentError::InternalServerError)?;
+        let mentioned = MentionService::resolve(&mut tx, comment.task_id, body).await?;
+        let comment: Comment = sqlx::query_as(
+            r#"
+            UPDATE task_comments
+            SET body = $1, body_html = $2, updated_at = $3
+            WHERE id = $4
+            RETURNING id, task_id, author_id, body, body_html, created_at, updated_at
+            "#,
+        )
+        .bind(body)
+        .bind(&mentioned.html)
+        .bind(chrono::Utc::now())
+        .bind(comment_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| CommentError::InternalServerError)?;
+        let newly_mentioned = MentionService::record(
+            &mut tx,
+            comment.task_id,
+            Some(comment.id),
+            user_id,
+            &mentioned.user_ids,
+        )
+        .await?;
+        tx.commit()
+            .await
+            .map_err(|_| CommentError::InternalServerError)?;
+
+        MentionService::notify(pool, comment.task_id, user_id, &newly_mentioned).await;
+        Ok(comment)
+    }
+
+    /// The author or anyone who can manage the task may delete a comment.
+    pub async fn delete_comment(
+        pool: &PgPool,
+        comment_id: i32,
+        user_id: i32,
+    ) -> Result<(), CommentError> {
+        let comment = Self::get_comment(pool, comment_id, user_id).await?;
+        if comment.author_id != Some(user_id) {
+            TaskService::require_permission(pool, comment.task_id, user_id, TaskPermission::Manage)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM task_comments WHERE id = $1")
+            .bind(comment_id)
+            .execute(pool)
+            .await
+            .map_err(|_| CommentError::InternalServerError)?;
+        Ok(())
+    }
+
+    /// A comment on a task the user can still see.
+    async fn get_comment(
+        pool: &PgPool,
+        comment_id: i32,
+        user_id: i32,
+    ) -> Result<Comment, CommentError> {
+        let comment: Comment = sqlx::query_as(
+            r#"
+            SELECT id, task_id, author_id, body, body_html, created_at, updated_at
+            FROM task_comments
+            WHERE id = $1
+            "#,
+        )
+        .bind(comment_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| CommentError::InternalServerError)?
+        .ok_or(CommentError::NotFound)?;
+        TaskService::require_permission(pool, comment.task_id, user_id, TaskPermission::View)
+            .await?;
+        Ok(comment)
+    }
+
+    /// Tells the task's creator and assignee about a new comment, unless they
+    /// wrote it, were just notified of a mention in it, or can no longer see
+    /// the task.
+    async fn notify_followers(pool: &PgPool, task_id: i32, author_id: i32, mentioned: &[i32]) {
+        let context: Result<(String, Option<i32>, Option<i32>, String), _> = sqlx::query_as(
+            r#"
+            SELECT t.task_name, t.user_id, t.assignee_id, u.email
+            FROM tasks t, users u
+            WHERE t.id = $1 AND u.id = $2
+            "#,
+        )
+        .bind(task_id)
+        .bind(author_id)
+        .fetch_one(pool)
+        .await;
+        let (task_name, creator_id, assignee_id, author_email) = match context {
+            Ok(context) => context,
+            Err(err) => {
+                tracing::error!("Failed to look up comment context: {}", err);
+                return;
+            }
+        };
+
+        let notification = NewNotification {
+            notification_type: NotificationType::Commented,
+            title: "New comment".to_string(),
+            body: format!("{} commented on \"{}\".", author_email, task_name),
+            link: format!("http://localhost:3000/tasks/{}", task_id),
+        };
+        let mut followers: Vec<i32> = creator_id.into_iter().collect();
+        followers.extend(assignee_id.filter(|&assignee_id| Some(assignee_id) != creator_id));
+        for follower_id in followers {
+            if follower_id == author_id || mentioned.contains(&follower_id) {
+                continue;
+            }
+            if TaskService::get_permission(pool, task_id, follower_id)
+                .await
+                .is_ok()
+            {
+                Notifier::notify(pool, follower_id, &notification).await;
+            }
+        }
+    }
+
+    fn validate_body(body: &str) -> Result<&str, CommentError> {
+        let body = body.trim();
+        if body.is_empty() || body.chars().count() > COMMENT_MAX_LENGTH {
+            return Err(CommentError::BadRequest);
+        }
+        Ok(body)
+    }
+}
@@ -1,3 +1,10 @@
+pub mod admin;
 pub mod auth;
+pub mod comments;
+pub mod notifications;
+pub mod projects;
+pub mod reminders;
+pub mod shares;
 pub mod tasks;
 pub mod users;
+pub mod workspaces;
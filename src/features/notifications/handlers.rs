@@ -0,0 +1,45 @@
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::{
+    models::Claims,
+    scopes::{RequireScope, UsersRead, UsersWrite},
+};
+
+use super::{
+    models::{Notification, NotificationError, NotificationQueryDto},
+    services::NotificationService,
+};
+
+pub async fn get_notifications(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersRead>,
+    Query(notification_query_dto): Query<NotificationQueryDto>,
+) -> Result<Json<Vec<Notification>>, NotificationError> {
+    let notifications =
+        NotificationService::get_notifications(&pool, claims.sub, notification_query_dto).await?;
+    Ok(Json(notifications))
+}
+
+pub async fn mark_read(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    Path(id): Path<i32>,
+) -> Result<Json<Notification>, NotificationError> {
+    let notification = NotificationService::mark_read(&pool, claims.sub, id).await?;
+    Ok(Json(notification))
+}
+
+pub async fn mark_all_read(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+) -> Result<Json<String>, NotificationError> {
+    let marked = NotificationService::mark_all_read(&pool, claims.sub).await?;
+    Ok(Json(format!("{} notifications marked as read", marked)))
+}
@@ -0,0 +1,58 @@
+use axum::{
+    body::Body,
+    http::{Response, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+/// Each type can be opted out of per channel in the user's preferences.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "notification_type", rename_all = "snake_case")]
+pub enum NotificationType {
+    Assigned,
+    Mentioned,
+    Commented,
+    Shared,
+    Reminder,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Notification {
+    pub id: i32,
+    #[serde(rename = "type")]
+    #[sqlx(rename = "type")]
+    pub notification_type: NotificationType,
+    pub title: String,
+    pub body: String,
+    pub link: String,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationQueryDto {
+    /// `true` lists only notifications not yet marked read.
+    #[serde(default)]
+    pub unread: bool,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+pub enum NotificationError {
+    NotFound,
+    InternalServerError,
+}
+
+impl IntoResponse for NotificationError {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            NotificationError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            NotificationError::InternalServerError => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use sqlx::PgPool;
+
+use crate::{features::users::preferences::PreferencesService, shared::mailer};
+
+use super::models::NotificationType;
+
+/// A notification to deliver; `link` points at what it is about.
+pub struct NewNotification {
+    pub notification_type: NotificationType,
+    pub title: String,
+    pub body: String,
+    pub link: String,
+}
+
+impl NewNotification {
+    fn email_body(&self) -> String {
+        format!("{}\n\n{}", self.body, self.link)
+    }
+}
+
+/// The one way features tell users about things. Delivery is best effort:
+/// failures are logged rather than returned, so a notification never undoes
+/// the change that caused it.
+pub struct Notifier;
+
+impl Notifier {
+    /// Stores the notification in-app and emails it, as far as the user's
+    /// preferences for its type allow.
+    pub async fn notify(pool: &PgPool, user_id: i32, notification: &NewNotification) {
+        let channels = match PreferencesService::get_preferences(pool, user_id).await {
+            Ok(preferences) => preferences
+                .notifications
+                .channels(notification.notification_type),
+            Err(_) => {
+                tracing::error!(
+                    "Failed to load notification preferences for user {}",
+                    user_id
+                );
+                return;
+            }
+        };
+
+        if channels.in_app {
+            let stored = sqlx::query(
+                r#"
+                INSERT INTO notifications (user_id, type, title, body, link, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+            )
+            .bind(user_id)
+            .bind(notification.notification_type)
+            .bind(&notification.title)
+            .bind(&notification.body)
+            .bind(&notification.link)
+            .bind(chrono::Utc::now())
+            .execute(pool)
+            .await;
+            if let Err(err) = stored {
+                tracing::error!("Failed to store notification: {}", err);
+            }
+        }
+
+        if channels.email {
+            let email: Result<(String,), _> =
+                sqlx::query_as("SELECT email FROM users WHERE id = $1")
+                    .bind(user_id)
+                    .fetch_one(pool)
+                    .await;
+            match email {
+                Ok((email,)) => Self::notify_email(&email, notification).await,
+                Err(err) => tracing::error!("Failed to look up notification email: {}", err),
+            }
+        }
+    }
+
+    /// Emails someone who may not have an account, such as the recipient of a
+    /// share to a new address. There are no preferences to check.
+    pub async fn notify_email(email: &str, notification: &NewNotification) {
+        if let Err(err) =
+            mailer::send_email(email, &notification.title, notification.email_body()).await
+        {
+            tracing::error!("Failed to send notification email: {}", err);
+        }
+    }
+}
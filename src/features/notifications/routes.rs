@@ -0,0 +1,19 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::middlewares::jwt_middleware;
+
+use super::handlers;
+
+pub fn notification_routes(pool: PgPool) -> Router {
+    Router::new()
+        .route("/notifications", get(handlers::get_notifications))
+        .route("/notifications/read", post(handlers::mark_all_read))
+        .route("/notifications/{id}/read", post(handlers::mark_read))
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
+        .with_state(pool)
+}
@@ -0,0 +1,77 @@
+use sqlx::PgPool;
+
+use super::models::{Notification, NotificationError, NotificationQueryDto};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+pub struct NotificationService;
+
+impl NotificationService {
+    pub async fn get_notifications(
+        pool: &PgPool,
+        user_id: i32,
+        notification_query_dto: NotificationQueryDto,
+    ) -> Result<Vec<Notification>, NotificationError> {
+        let NotificationQueryDto {
+            unread,
+            limit,
+            offset,
+        } = notification_query_dto;
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let offset = offset.unwrap_or(0).max(0);
+
+        sqlx::query_as(
+            r#"
+            SELECT id, type, title, body, link, read_at, created_at
+            FROM notifications
+            WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(unread)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| NotificationError::InternalServerError)
+    }
+
+    /// Marking an already read notification keeps its original `read_at`.
+    pub async fn mark_read(
+        pool: &PgPool,
+        user_id: i32,
+        notification_id: i32,
+    ) -> Result<Notification, NotificationError> {
+        sqlx::query_as(
+            r#"
+            UPDATE notifications
+            SET read_at = COALESCE(read_at, $3)
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, type, title, body, link, read_at, created_at
+            "#,
+        )
+        .bind(notification_id)
+        .bind(user_id)
+        .bind(chrono::Utc::now())
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| NotificationError::InternalServerError)?
+        .ok_or(NotificationError::NotFound)
+    }
+
+    /// Returns how many notifications were marked.
+    pub async fn mark_all_read(pool: &PgPool, user_id: i32) -> Result<u64, NotificationError> {
+        let result = sqlx::query(
+            "UPDATE notifications SET read_at = $2 WHERE user_id = $1 AND read_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await
+        .map_err(|_| NotificationError::InternalServerError)?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::{
+    models::Claims,
+    scopes::{RequireScope, TasksRead, TasksWrite},
+};
+
+use super::{
+    models::{CreateProjectDto, Project, ProjectError, ProjectFilterDto, UpdateProjectDto},
+    services::ProjectService,
+};
+
+pub async fn get_projects(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Query(project_filter_dto): Query<ProjectFilterDto>,
+) -> Result<Json<Vec<Project>>, ProjectError> {
+    let projects =
+        ProjectService::get_projects(&pool, claims.sub, project_filter_dto.workspace_id).await?;
+    Ok(Json(projects))
+}
+
+pub async fn get_project(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Path(id): Path<i32>,
+) -> Result<Json<Project>, ProjectError> {
+    let project = ProjectService::get_project(&pool, id, claims.sub).await?;
+    Ok(Json(project))
+}
+
+pub async fn create_project(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Json(create_project_dto): Json<CreateProjectDto>,
+) -> Result<Json<Project>, ProjectError> {
+    let project = ProjectService::create_project(&pool, claims.sub, create_project_dto).await?;
+    Ok(Json(project))
+}
+
+pub async fn update_project(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+    Json(update_project_dto): Json<UpdateProjectDto>,
+) -> Result<Json<Project>, ProjectError> {
+    let project =
+        ProjectService::rename_project(&pool, id, claims.sub, &update_project_dto.name).await?;
+    Ok(Json(project))
+}
+
+pub async fn delete_project(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ProjectError> {
+    ProjectService::delete_project(&pool, id, claims.sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
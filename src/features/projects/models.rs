@@ -0,0 +1,64 @@
+use axum::{
+    body::Body,
+    http::{Response, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::features::workspaces::models::WorkspaceError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct Project {
+    pub id: i32,
+    pub workspace_id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateProjectDto {
+    /// Defaults to the caller's personal workspace.
+    pub workspace_id: Option<i32>,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateProjectDto {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectFilterDto {
+    pub workspace_id: Option<i32>,
+}
+
+pub enum ProjectError {
+    NotFound,
+    Forbidden,
+    BadRequest,
+    InternalServerError,
+}
+
+impl From<WorkspaceError> for ProjectError {
+    fn from(err: WorkspaceError) -> Self {
+        match err {
+            WorkspaceError::NotFound => ProjectError::NotFound,
+            WorkspaceError::Forbidden => ProjectError::Forbidden,
+            WorkspaceError::BadRequest | WorkspaceError::Conflict => ProjectError::BadRequest,
+            WorkspaceError::InternalServerError => ProjectError::InternalServerError,
+        }
+    }
+}
+
+impl IntoResponse for ProjectError {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            ProjectError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            ProjectError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            ProjectError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+            ProjectError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
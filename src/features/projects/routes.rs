@@ -0,0 +1,22 @@
+use axum::{middleware, routing::get, Router};
+use sqlx::PgPool;
+
+use crate::features::auth::middlewares::jwt_middleware;
+
+use super::handlers;
+
+pub fn project_routes(pool: PgPool) -> Router {
+    Router::new()
+        .route(
+            "/projects",
+            get(handlers::get_projects).post(handlers::create_project),
+        )
+        .route(
+            "/projects/{id}",
+            get(handlers::get_project)
+                .patch(handlers::update_project)
+                .delete(handlers::delete_project),
+        )
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
+        .with_state(pool)
+}
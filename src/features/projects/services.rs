@@ -0,0 +1,134 @@
+use sqlx::PgPool;
+
+use crate::features::workspaces::{models::WorkspaceRole, services::WorkspaceService};
+
+use super::models::{CreateProjectDto, Project, ProjectError};
+
+const PROJECT_NAME_MAX_LENGTH: usize = 100;
+
+pub struct ProjectService;
+
+impl ProjectService {
+    /// Projects in every workspace the user belongs to, or just one of them.
+    pub async fn get_projects(
+        pool: &PgPool,
+        user_id: i32,
+        workspace_id: Option<i32>,
+    ) -> Result<Vec<Project>, ProjectError> {
+        sqlx::query_as(
+            r#"
+            SELECT p.id, p.workspace_id, p.name, p.created_at
+            FROM projects p
+            JOIN workspace_members m ON m.workspace_id = p.workspace_id
+            WHERE m.user_id = $1 AND ($2::INT IS NULL OR p.workspace_id = $2)
+            ORDER BY p.name
+            "#,
+        )
+        .bind(user_id)
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| ProjectError::InternalServerError)
+    }
+
+    pub async fn get_project(
+        pool: &PgPool,
+        project_id: i32,
+        user_id: i32,
+    ) -> Result<Project, ProjectError> {
+        sqlx::query_as(
+            r#"
+            SELECT p.id, p.workspace_id, p.name, p.created_at
+            FROM projects p
+            JOIN workspace_members m ON m.workspace_id = p.workspace_id
+            WHERE p.id = $1 AND m.user_id = $2
+            "#,
+        )
+        .bind(project_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| ProjectError::InternalServerError)?
+        .ok_or(ProjectError::NotFound)
+    }
+
+    pub async fn create_project(
+        pool: &PgPool,
+        user_id: i32,
+        create_project_dto: CreateProjectDto,
+    ) -> Result<Project, ProjectError> {
+        let CreateProjectDto { workspace_id, name } = create_project_dto;
+        let name = Self::validate_name(&name)?;
+        let workspace_id = match workspace_id {
+            Some(workspace_id) => workspace_id,
+            None => WorkspaceService::personal_workspace_id(pool, user_id).await?,
+        };
+        WorkspaceService::require_role(pool, workspace_id, user_id, WorkspaceRole::Member).await?;
+
+        sqlx::query_as(
+            r#"
+            INSERT INTO projects (workspace_id, name, created_at)
+            VALUES ($1, $2, $3)
+            RETURNING id, workspace_id, name, created_at
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(name)
+        .bind(chrono::Utc::now())
+        .fetch_one(pool)
+        .await
+        .map_err(|_| ProjectError::InternalServerError)
+    }
+
+    pub async fn rename_project(
+        pool: &PgPool,
+        project_id: i32,
+        user_id: i32,
+        name: &str,
+    ) -> Result<Project, ProjectError> {
+        let project = Self::get_project(pool, project_id, user_id).await?;
+        WorkspaceService::require_role(pool, project.workspace_id, user_id, WorkspaceRole::Member)
+            .await?;
+        let name = Self::validate_name(name)?;
+
+        sqlx::query_as(
+            r#"
+            UPDATE projects
+            SET name = $1
+            WHERE id = $2
+            RETURNING id, workspace_id, name, created_at
+            "#,
+        )
+        .bind(name)
+        .bind(project_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|_| ProjectError::InternalServerError)
+    }
+
+    /// Tasks in the project stay in the workspace, without a project.
+    pub async fn delete_project(
+        pool: &PgPool,
+        project_id: i32,
+        user_id: i32,
+    ) -> Result<(), ProjectError> {
+        let project = Self::get_project(pool, project_id, user_id).await?;
+        WorkspaceService::require_role(pool, project.workspace_id, user_id, WorkspaceRole::Admin)
+            .await?;
+
+        sqlx::query("DELETE FROM projects WHERE id = $1")
+            .bind(project_id)
+            .execute(pool)
+            .await
+            .map_err(|_| ProjectError::InternalServerError)?;
+        Ok(())
+    }
+
+    fn validate_name(name: &str) -> Result<&str, ProjectError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > PROJECT_NAME_MAX_LENGTH {
+            return Err(ProjectError::BadRequest);
+        }
+        Ok(name)
+    }
+}
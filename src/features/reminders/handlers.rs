@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::{
+    models::Claims,
+    scopes::{RequireScope, TasksRead, TasksWrite},
+};
+
+use super::{
+    models::{CreateReminderDto, Reminder, ReminderError},
+    services::ReminderService,
+};
+
+pub async fn get_reminders(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Path(task_id): Path<i32>,
+) -> Result<Json<Vec<Reminder>>, ReminderError> {
+    let reminders = ReminderService::get_reminders(&pool, task_id, claims.sub).await?;
+    Ok(Json(reminders))
+}
+
+pub async fn create_reminder(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(task_id): Path<i32>,
+    Json(create_reminder_dto): Json<CreateReminderDto>,
+) -> Result<Json<Reminder>, ReminderError> {
+    let reminder =
+        ReminderService::create_reminder(&pool, task_id, claims.sub, create_reminder_dto).await?;
+    Ok(Json(reminder))
+}
+
+pub async fn delete_reminder(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ReminderError> {
+    ReminderService::delete_reminder(&pool, id, claims.sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,60 @@
+use axum::{
+    body::Body,
+    http::{Response, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::features::tasks::models::TaskError;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Reminder {
+    pub id: i32,
+    pub task_id: i32,
+    pub remind_at: Option<DateTime<Utc>>,
+    pub before_due_minutes: Option<i32>,
+    /// When the reminder goes out; `None` for a relative reminder on a task
+    /// without a due date.
+    pub fires_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Exactly one of `remind_at` and `before_due` is required. `before_due` is a
+/// number and a unit, e.g. `30m`, `1h`, `2d` or `1w`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateReminderDto {
+    pub remind_at: Option<DateTime<Utc>>,
+    pub before_due: Option<String>,
+}
+
+pub enum ReminderError {
+    NotFound,
+    Forbidden,
+    BadRequest,
+    InternalServerError,
+}
+
+impl From<TaskError> for ReminderError {
+    fn from(err: TaskError) -> Self {
+        match err {
+            TaskError::NotFound => ReminderError::NotFound,
+            TaskError::Forbidden => ReminderError::Forbidden,
+            TaskError::BadRequest => ReminderError::BadRequest,
+            TaskError::InternalServerError => ReminderError::InternalServerError,
+        }
+    }
+}
+
+impl IntoResponse for ReminderError {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            ReminderError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            ReminderError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            ReminderError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+            ReminderError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
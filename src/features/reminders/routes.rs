@@ -0,0 +1,21 @@
+use axum::{
+    middleware,
+    routing::{delete, get},
+    Router,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::middlewares::jwt_middleware;
+
+use super::handlers;
+
+pub fn reminder_routes(pool: PgPool) -> Router {
+    Router::new()
+        .route(
+            "/tasks/{id}/reminders",
+            get(handlers::get_reminders).post(handlers::create_reminder),
+        )
+        .route("/reminders/{id}", delete(handlers::delete_reminder))
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
+        .with_state(pool)
+}
@@ -0,0 +1,303 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{prelude::FromRow, PgPool};
+
+use crate::{
+    features::{
+        notifications::{
+            models::NotificationType,
+            notifier::{NewNotification, Notifier},
+        },
+        tasks::{models::TaskPermission, services::TaskService},
+    },
+    shared::time,
+};
+
+use super::models::{CreateReminderDto, Reminder, ReminderError};
+
+const REMINDER_POLL_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_BEFORE_DUE_MINUTES: i64 = 60 * 24 * 365;
+
+#[derive(FromRow)]
+struct DueReminder {
+    id: i32,
+    user_id: i32,
+    task_id: i32,
+    task_name: String,
+    completed: bool,
+    due_at: Option<DateTime<Utc>>,
+    timezone: String,
+}
+
+pub struct ReminderService;
+
+impl ReminderService {
+    /// The caller's own reminders on a task.
+    pub async fn get_reminders(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+    ) -> Result<Vec<Reminder>, ReminderError> {
+        TaskService::require_permission(pool, task_id, user_id, TaskPermission::View).await?;
+
+        sqlx::query_as(
+            r#"
+            SELECT r.id, r.task_id, r.remind_at, r.before_due_minutes,
+                   COALESCE(r.remind_at, t.due_at - make_interval(mins => r.before_due_minutes))
+                       AS fires_at,
+                   r.delivered_at, r.created_at
+            FROM task_reminders r
+            JOIN tasks t ON t.id = r.task_id
+            WHERE r.task_id = $1 AND r.user_id = $2
+            ORDER BY fires_at NULLS LAST, r.id
+            "#,
+        )
+        .bind(task_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| ReminderError::InternalServerError)
+    }
+
+    /// Anyone who can see a task can set reminders on it for themselves.
+    /// Relative reminders need the task to have a due date.
+    pub async fn create_reminder(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+        create_reminder_dto: CreateReminderDto,
+    ) -> Result<Reminder, ReminderError> {
+        TaskService::require_permission(pool, task_id, user_id, TaskPermission::View).await?;
+
+        let (remind_at, before_due_minutes) = match create_reminder_dto {
+            CreateReminderDto {
+                remind_at: Some(remind_at),
+                before_due: None,
+            } if remind_at > chrono::Utc::now() => (Some(remind_at), None),
+            CreateReminderDto {
+                remind_at: None,
+                before_due: Some(before_due),
+            } => {
+                let minutes =
+                    Self::parse_before_due(&before_due).ok_or(ReminderError::BadRequest)?;
+                (None, Some(minutes))
+            }
+            _ => return Err(ReminderError::BadRequest),
+        };
+        if before_due_minutes.is_some() {
+            let (due_at,): (Option<DateTime<Utc>>,) =
+                sqlx::query_as("SELECT due_at FROM tasks WHERE id = $1")
+                    .bind(task_id)
+                    .fetch_one(pool)
+                    .await
+                    .map_err(|_| ReminderError::InternalServerError)?;
+            if due_at.is_none() {
+                return Err(ReminderError::BadRequest);
+            }
+        }
+
+        let (reminder_id,): (i32,) = sqlx::query_as(
+            r#"
+            INSERT INTO task_reminders (task_id, user_id, remind_at, before_due_minutes, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#,
+        )
+        .bind(task_id)
+        .bind(user_id)
+        .bind(remind_at)
+        .bind(before_due_minutes)
+        .bind(chrono::Utc::now())
+        .fetch_one(pool)
+        .await
+        .map_err(|_| ReminderError::InternalServerError)?;
+
+        sqlx::query_as(
+            r#"
+            SELECT r.id, r.task_id, r.remind_at, r.before_due_minutes,
+                   COALESCE(r.remind_at, t.due_at - make_interval(mins => r.before_due_minutes))
+                       AS fires_at,
+                   r.delivered_at, r.created_at
+            FROM task_reminders r
+            JOIN tasks t ON t.id = r.task_id
+            WHERE r.id = $1
+            "#,
+        )
+        .bind(reminder_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|_| ReminderError::InternalServerError)
+    }
+
+    pub async fn delete_reminder(
+        pool: &PgPool,
+        reminder_id: i32,
+        user_id: i32,
+    ) -> Result<(), ReminderError> {
+        let result = sqlx::query("DELETE FROM task_reminders WHERE id = $1 AND user_id = $2")
+            .bind(reminder_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .map_err(|_| ReminderError::InternalServerError)?;
+
+        match result.rows_affected() {
+            0 => Err(ReminderError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends every reminder that is due, one transaction per reminder. The row
+    /// is locked so concurrent schedulers skip it, and it is marked delivered
+    /// and committed before the notification goes out: a crash in between
+    /// loses that reminder rather than sending it twice. Reminders on
+    /// completed tasks, or on tasks the user can no longer see, are marked
+    /// delivered without sending. Returns how many were sent.
+    pub async fn deliver_due_reminders(pool: &PgPool) -> Result<u64, sqlx::Error> {
+        let mut delivered = 0;
+        loop {
+            let mut tx = pool.begin().await?;
+            let reminder: Option<DueReminder> = sqlx::query_as(
+                r#"
+                    SELECT r.id, r.user_id, r.task_id, t.task_name,
+                           t.task_status = 'completed' AS completed, t.due_at, u.timezone
+                    FROM task_reminders r
+                    JOIN tasks t ON t.id = r.task_id
+                    JOIN users u ON u.id = r.user_id
+                    WHERE r.delivered_at IS NULL
+                        AND COALESCE(
+                            r.remind_at,
+                            t.due_at - make_interval(mins => r.before_due_minutes)
+                        ) <= $1
+                    LIMIT 1
+                    FOR UPDATE OF r SKIP LOCKED
+                    "#,
+            )
+            .bind(chrono::Utc::now())
+            .fetch_optional(&mut *tx)
+            .await?;
+            let Some(DueReminder {
+                id: reminder_id,
+                user_id,
+                task_id,
+                task_name,
+                completed,
+                due_at,
+                timezone,
+            }) = reminder
+            else {
+                return Ok(delivered);
+            };
+
+            let can_see = TaskService::get_permission(pool, task_id, user_id)
+                .await
+                .is_ok();
+            sqlx::query("UPDATE task_reminders SET delivered_at = $2 WHERE id = $1")
+                .bind(reminder_id)
+                .bind(chrono::Utc::now())
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            if completed || !can_see {
+                continue;
+            }
+            let body = match due_at {
+                Some(due_at) => format!(
+                    "\"{}\" is due {}.",
+                    task_name,
+                    time::format_local(due_at, &timezone)
+                ),
+                None => format!("You asked to be reminded about \"{}\".", task_name),
+            };
+            let notification = NewNotification {
+                notification_type: NotificationType::Reminder,
+                title: format!("Reminder: {}", task_name),
+                body,
+                link: format!("http://localhost:3000/tasks/{}", task_id),
+            };
+            Notifier::notify(pool, user_id, &notification).await;
+            delivered += 1;
+        }
+    }
+
+    pub async fn run_reminder_scheduler(pool: PgPool) {
+        let mut interval = tokio::time::interval(REMINDER_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = Self::deliver_due_reminders(&pool).await {
+                tracing::error!("Failed to deliver reminders: {}", err);
+            }
+        }
+    }
+
+    /// Minutes in `30m`, `1h`, `2d` or `1w`, up to a year.
+    fn parse_before_due(before_due: &str) -> Option<i32> {
+        let before_due = before_due.trim();
+        let unit = before_due.chars().last()?;
+        let amount: i64 = before_due[..before_due.len() - unit.len_utf8()]
+            .trim()
+            .parse()
+            .ok()?;
+        let minutes = match unit {
+            'm' => amount,
+            'h' => amount.checked_mul(60)?,
+            'd' => amount.checked_mul(60 * 24)?,
+            'w' => amount.checked_mul(60 * 24 * 7)?,
+            _ => return None,
+        };
+        if !(0..=MAX_BEFORE_DUE_MINUTES).contains(&minutes) {
+            return None;
+        }
+        i32::try_from(minutes).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_unit() {
+        assert_eq!(ReminderService::parse_before_due("30m"), Some(30));
+        assert_eq!(ReminderService::parse_before_due("1h"), Some(60));
+        assert_eq!(ReminderService::parse_before_due("2d"), Some(2 * 24 * 60));
+        assert_eq!(ReminderService::parse_before_due("1w"), Some(7 * 24 * 60));
+        assert_eq!(ReminderService::parse_before_due("0m"), Some(0));
+    }
+
+    #[test]
+    fn allows_surrounding_whitespace() {
+        assert_eq!(ReminderService::parse_before_due(" 15 m "), Some(15));
+    }
+
+    #[test]
+    fn limits_to_a_year() {
+        assert_eq!(
+            ReminderService::parse_before_due(&format!("{}m", MAX_BEFORE_DUE_MINUTES)),
+            i32::try_from(MAX_BEFORE_DUE_MINUTES).ok()
+        );
+        assert_eq!(
+            ReminderService::parse_before_due(&format!("{}m", MAX_BEFORE_DUE_MINUTES + 1)),
+            None
+        );
+        assert_eq!(ReminderService::parse_before_due("53w"), None);
+        assert_eq!(
+            ReminderService::parse_before_due(&format!("{}w", i64::MAX)),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        for before_due in ["", "m", "30", "30s", "-1h", "1.5h", "h1", "1 hour", "1é"] {
+            assert_eq!(
+                ReminderService::parse_before_due(before_due),
+                None,
+                "{:?}",
+                before_due
+            );
+        }
+    }
+}
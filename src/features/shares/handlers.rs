@@ -0,0 +1,71 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::features::auth::{
+    models::Claims,
+    scopes::{RequireScope, TasksRead, TasksWrite},
+};
+
+use super::{
+    models::{CreateShareDto, ShareError, ShareFilterDto, ShareGrant, UpdateShareDto},
+    services::ShareService,
+};
+
+pub async fn get_shares(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Query(share_filter_dto): Query<ShareFilterDto>,
+) -> Result<Json<Vec<ShareGrant>>, ShareError> {
+    let shares = ShareService::get_shares(&pool, claims.sub, share_filter_dto).await?;
+    Ok(Json(shares))
+}
+
+pub async fn get_received_shares(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+) -> Result<Json<Vec<ShareGrant>>, ShareError> {
+    let shares = ShareService::get_received_shares(&pool, claims.sub).await?;
+    Ok(Json(shares))
+}
+
+pub async fn create_share(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Json(create_share_dto): Json<CreateShareDto>,
+) -> Result<Json<ShareGrant>, ShareError> {
+    if create_share_dto.validate().is_err() {
+        return Err(ShareError::BadRequest);
+    }
+    let share = ShareService::create_share(&pool, claims.sub, create_share_dto).await?;
+    Ok(Json(share))
+}
+
+pub async fn update_share(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+    Json(update_share_dto): Json<UpdateShareDto>,
+) -> Result<Json<ShareGrant>, ShareError> {
+    let share =
+        ShareService::update_share(&pool, claims.sub, id, update_share_dto.permission).await?;
+    Ok(Json(share))
+}
+
+pub async fn revoke_share(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ShareError> {
+    ShareService::revoke_share(&pool, claims.sub, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,109 @@
+use axum::{
+    body::Body,
+    http::{Response, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use validator::Validate;
+
+use crate::features::{
+    projects::models::ProjectError, tasks::models::TaskError, workspaces::models::WorkspaceError,
+};
+
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "share_permission", rename_all = "lowercase")]
+pub enum SharePermission {
+    View,
+    Edit,
+}
+
+/// Access to one project (and its tasks) or one task for one email address.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ShareGrant {
+    pub id: i32,
+    pub project_id: Option<i32>,
+    pub task_id: Option<i32>,
+    pub email: String,
+    pub permission: SharePermission,
+    /// No verified account has this email yet.
+    pub pending: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Exactly one of `project_id` and `task_id` must be set.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateShareDto {
+    pub project_id: Option<i32>,
+    pub task_id: Option<i32>,
+    #[validate(email(message = "Invalid email"))]
+    pub email: String,
+    pub permission: SharePermission,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateShareDto {
+    pub permission: SharePermission,
+}
+
+/// Exactly one of `project_id` and `task_id` must be set.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareFilterDto {
+    pub project_id: Option<i32>,
+    pub task_id: Option<i32>,
+}
+
+pub enum ShareError {
+    NotFound,
+    Forbidden,
+    BadRequest,
+    InternalServerError,
+}
+
+impl From<TaskError> for ShareError {
+    fn from(err: TaskError) -> Self {
+        match err {
+            TaskError::NotFound => ShareError::NotFound,
+            TaskError::Forbidden => ShareError::Forbidden,
+            TaskError::BadRequest => ShareError::BadRequest,
+            TaskError::InternalServerError => ShareError::InternalServerError,
+        }
+    }
+}
+
+impl From<ProjectError> for ShareError {
+    fn from(err: ProjectError) -> Self {
+        match err {
+            ProjectError::NotFound => ShareError::NotFound,
+            ProjectError::Forbidden => ShareError::Forbidden,
+            ProjectError::BadRequest => ShareError::BadRequest,
+            ProjectError::InternalServerError => ShareError::InternalServerError,
+        }
+    }
+}
+
+impl From<WorkspaceError> for ShareError {
+    fn from(err: WorkspaceError) -> Self {
+        match err {
+            WorkspaceError::NotFound => ShareError::NotFound,
+            WorkspaceError::Forbidden => ShareError::Forbidden,
+            WorkspaceError::BadRequest | WorkspaceError::Conflict => ShareError::BadRequest,
+            WorkspaceError::InternalServerError => ShareError::InternalServerError,
+        }
+    }
+}
+
+impl IntoResponse for ShareError {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            ShareError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            ShareError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            ShareError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+            ShareError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use axum::{
+    middleware,
+    routing::{get, patch},
+    Router,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::middlewares::jwt_middleware;
+
+use super::handlers;
+
+pub fn share_routes(pool: PgPool) -> Router {
+    Router::new()
+        .route(
+            "/shares",
+            get(handlers::get_shares).post(handlers::create_share),
+        )
+        .route("/shares/received", get(handlers::get_received_shares))
+        .route(
+            "/shares/{id}",
+            patch(handlers::update_share).delete(handlers::revoke_share),
+        )
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
+        .with_state(pool)
+}
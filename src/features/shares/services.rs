@@ -0,0 +1,303 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    features::{
+        notifications::{
+            models::NotificationType,
+            notifier::{NewNotification, Notifier},
+        },
+        projects::services::ProjectService,
+        tasks::{models::TaskPermission, services::TaskService},
+        workspaces::{models::WorkspaceRole, services::WorkspaceService},
+    },
+    shared::time,
+};
+
+use super::models::{CreateShareDto, ShareError, ShareFilterDto, ShareGrant, SharePermission};
+
+enum SharedResource {
+    Project(i32),
+    Task(i32),
+}
+
+impl SharedResource {
+    fn from_ids(project_id: Option<i32>, task_id: Option<i32>) -> Result<Self, ShareError> {
+        match (project_id, task_id) {
+            (Some(project_id), None) => Ok(SharedResource::Project(project_id)),
+            (None, Some(task_id)) => Ok(SharedResource::Task(task_id)),
+            _ => Err(ShareError::BadRequest),
+        }
+    }
+}
+
+pub struct ShareService;
+
+impl ShareService {
+    /// Shares a project or task by email, or changes the permission of an
+    /// existing grant for that email. People without an account are told to
+    /// sign up; the grant applies once their email is verified.
+    pub async fn create_share(
+        pool: &PgPool,
+        user_id: i32,
+        create_share_dto: CreateShareDto,
+    ) -> Result<ShareGrant, ShareError> {
+        let CreateShareDto {
+            project_id,
+            task_id,
+            email,
+            permission,
+        } = create_share_dto;
+        let resource = SharedResource::from_ids(project_id, task_id)?;
+        let resource_name = Self::require_manage(pool, user_id, &resource).await?;
+
+        let (sharer_email,): (String,) = sqlx::query_as("SELECT email FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|_| ShareError::InternalServerError)?;
+        if sharer_email.eq_ignore_ascii_case(&email) {
+            return Err(ShareError::BadRequest);
+        }
+
+        let conflict_target = match resource {
+            SharedResource::Project(_) => {
+                "(project_id, lower(grantee_email)) WHERE project_id IS NOT NULL"
+            }
+            SharedResource::Task(_) => "(task_id, lower(grantee_email)) WHERE task_id IS NOT NULL",
+        };
+        let (share_id,): (i32,) = sqlx::query_as(&format!(
+            r#"
+            INSERT INTO share_grants
+                (project_id, task_id, grantee_email, permission, granted_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT {}
+            DO UPDATE SET permission = EXCLUDED.permission
+            RETURNING id
+            "#,
+            conflict_target
+        ))
+        .bind(project_id)
+        .bind(task_id)
+        .bind(&email)
+        .bind(permission)
+        .bind(user_id)
+        .bind(chrono::Utc::now())
+        .fetch_one(pool)
+        .await
+        .map_err(|_| ShareError::InternalServerError)?;
+        let share = Self::get_share(pool, share_id).await?;
+
+        let (link, due_at) = match resource {
+            SharedResource::Project(id) => (format!("http://localhost:3000/projects/{}", id), None),
+            SharedResource::Task(id) => {
+                let (due_at,): (Option<DateTime<Utc>>,) =
+                    sqlx::query_as("SELECT due_at FROM tasks WHERE id = $1")
+                        .bind(id)
+                        .fetch_one(pool)
+                        .await
+                        .map_err(|_| ShareError::InternalServerError)?;
+                (format!("http://localhost:3000/tasks/{}", id), due_at)
+            }
+        };
+        let grantee: Option<(i32, String)> = sqlx::query_as(
+            "SELECT id, timezone FROM users WHERE lower(email) = lower($1) AND verified",
+        )
+        .bind(&email)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| ShareError::InternalServerError)?;
+        // Without an account there's no time zone to show the due date in.
+        let due = |timezone: &str| match due_at {
+            Some(due_at) => format!(" It's due {}.", time::format_local(due_at, timezone)),
+            None => String::new(),
+        };
+        match grantee {
+            Some((grantee_id, timezone)) => {
+                let notification = NewNotification {
+                    notification_type: NotificationType::Shared,
+                    title: "Something was shared with you".to_string(),
+                    body: format!(
+                        "{} shared \"{}\" with you.{}",
+                        sharer_email,
+                        resource_name,
+                        due(&timezone)
+                    ),
+                    link,
+                };
+                Notifier::notify(pool, grantee_id, &notification).await;
+            }
+            None => {
+                let notification = NewNotification {
+                    notification_type: NotificationType::Shared,
+                    title: "Something was shared with you".to_string(),
+                    body: format!(
+                        "{} shared \"{}\" with you.{} Create an account with this email address to open it.",
+                        sharer_email,
+                        resource_name,
+                        due("UTC")
+                    ),
+                    link: "http://localhost:3000/register".to_string(),
+                };
+                Notifier::notify_email(&email, &notification).await;
+            }
+        }
+
+        Ok(share)
+    }
+
+    /// Grants on one project or task, for those allowed to manage them.
+    pub async fn get_shares(
+        pool: &PgPool,
+        user_id: i32,
+        share_filter_dto: ShareFilterDto,
+    ) -> Result<Vec<ShareGrant>, ShareError> {
+        let ShareFilterDto {
+            project_id,
+            task_id,
+        } = share_filter_dto;
+        let resource = SharedResource::from_ids(project_id, task_id)?;
+        Self::require_manage(pool, user_id, &resource).await?;
+
+        sqlx::query_as(
+            r#"
+            SELECT g.id, g.project_id, g.task_id, g.grantee_email AS email, g.permission,
+                   NOT EXISTS (
+                       SELECT 1 FROM users u
+                       WHERE lower(u.email) = lower(g.grantee_email) AND u.verified
+                   ) AS pending,
+                   g.created_at
+            FROM share_grants g
+            WHERE g.project_id = $1 OR g.task_id = $2
+            ORDER BY g.created_at
+            "#,
+        )
+        .bind(project_id)
+        .bind(task_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| ShareError::InternalServerError)
+    }
+
+    /// Grants made to the caller's verified email.
+    pub async fn get_received_shares(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<Vec<ShareGrant>, ShareError> {
+        sqlx::query_as(
+            r#"
+            SELECT g.id, g.project_id, g.task_id, g.grantee_email AS email, g.permission,
+                   FALSE AS pending, g.created_at
+            FROM share_grants g
+            JOIN users u ON lower(u.email) = lower(g.grantee_email)
+            WHERE u.id = $1 AND u.verified
+            ORDER BY g.created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| ShareError::InternalServerError)
+    }
+
+    pub async fn update_share(
+        pool: &PgPool,
+        user_id: i32,
+        share_id: i32,
+        permission: SharePermission,
+    ) -> Result<ShareGrant, ShareError> {
+        let share = Self::get_share(pool, share_id).await?;
+        let resource = SharedResource::from_ids(share.project_id, share.task_id)?;
+        Self::require_manage(pool, user_id, &resource).await?;
+
+        sqlx::query("UPDATE share_grants SET permission = $1 WHERE id = $2")
+            .bind(permission)
+            .bind(share_id)
+            .execute(pool)
+            .await
+            .map_err(|_| ShareError::InternalServerError)?;
+        Self::get_share(pool, share_id).await
+    }
+
+    /// Revokes a grant; the grantee may also drop one made to them. Access is
+    /// checked against the grants on every request, so this applies at once.
+    pub async fn revoke_share(
+        pool: &PgPool,
+        user_id: i32,
+        share_id: i32,
+    ) -> Result<(), ShareError> {
+        let share = Self::get_share(pool, share_id).await?;
+        let is_grantee: Option<(i32,)> = sqlx::query_as(
+            "SELECT id FROM users WHERE id = $1 AND verified AND lower(email) = lower($2)",
+        )
+        .bind(user_id)
+        .bind(&share.email)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| ShareError::InternalServerError)?;
+        if is_grantee.is_none() {
+            let resource = SharedResource::from_ids(share.project_id, share.task_id)?;
+            Self::require_manage(pool, user_id, &resource).await?;
+        }
+
+        sqlx::query("DELETE FROM share_grants WHERE id = $1")
+            .bind(share_id)
+            .execute(pool)
+            .await
+            .map_err(|_| ShareError::InternalServerError)?;
+        Ok(())
+    }
+
+    async fn get_share(pool: &PgPool, share_id: i32) -> Result<ShareGrant, ShareError> {
+        sqlx::query_as(
+            r#"
+            SELECT g.id, g.project_id, g.task_id, g.grantee_email AS email, g.permission,
+                   NOT EXISTS (
+                       SELECT 1 FROM users u
+                       WHERE lower(u.email) = lower(g.grantee_email) AND u.verified
+                   ) AS pending,
+                   g.created_at
+            FROM share_grants g
+            WHERE g.id = $1
+            "#,
+        )
+        .bind(share_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| ShareError::InternalServerError)?
+        .ok_or(ShareError::NotFound)
+    }
+
+    /// Only workspace members who can edit may share; returns the resource's
+    /// name for the email.
+    async fn require_manage(
+        pool: &PgPool,
+        user_id: i32,
+        resource: &SharedResource,
+    ) -> Result<String, ShareError> {
+        match *resource {
+            SharedResource::Task(task_id) => {
+                TaskService::require_permission(pool, task_id, user_id, TaskPermission::Manage)
+                    .await?;
+                let (task_name,): (String,) =
+                    sqlx::query_as("SELECT task_name FROM tasks WHERE id = $1")
+                        .bind(task_id)
+                        .fetch_one(pool)
+                        .await
+                        .map_err(|_| ShareError::InternalServerError)?;
+                Ok(task_name)
+            }
+            SharedResource::Project(project_id) => {
+                let project = ProjectService::get_project(pool, project_id, user_id).await?;
+                WorkspaceService::require_role(
+                    pool,
+                    project.workspace_id,
+                    user_id,
+                    WorkspaceRole::Member,
+                )
+                .await?;
+                Ok(project.name)
+            }
+        }
+    }
+}
@@ -2,98 +2,139 @@ use axum::{
     extract::{Path, Query, State},
     Extension, Json,
 };
+use serde_json::json;
 use sqlx::PgPool;
 
-use crate::features::auth::models::Claims;
+use crate::{
+    features::{
+        auth::{
+            models::Claims,
+            scopes::{RequireScope, TasksRead, TasksWrite},
+        },
+        workspaces::{models::WorkspaceRole, services::WorkspaceService},
+    },
+    shared::time,
+};
 
-use super::models::{CreateTaskDto, Task, TaskError, TaskFilterDto, UpdateTaskStatusDto};
+use super::{
+    mentions::{Mention, MentionService},
+    models::{
+        AssigneeFilter, CreateTaskDto, DueFilter, Task, TaskError, TaskEvent, TaskFilterDto,
+        TaskHistoryEntry, TaskPermission, TaskStatus, UpdateTaskAssigneeDto,
+        UpdateTaskDescriptionDto, UpdateTaskDueDateDto, UpdateTaskStatusDto,
+    },
+    services::TaskService,
+};
 
+/// Tasks from every workspace the caller belongs to, or with `shared=true`
+/// the tasks shared with them, narrowed by the filters. `assignee` takes
+/// `me`, `none` or a user id; `due` takes `today` or `overdue`.
 pub async fn get_tasks(
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
     Query(task_filter_dto): Query<TaskFilterDto>,
 ) -> Result<Json<Vec<Task>>, TaskError> {
     let user_id = claims.sub;
-    let tasks = match (task_filter_dto.search, task_filter_dto.task_status) {
-        (Some(search), Some(task_status)) => {
-            let search = format!("%{}%", search);
-            sqlx::query_as(
-                r#"
-                    SELECT id, task_name, task_status, created_at, user_id
-                    FROM tasks
-                    WHERE user_id = $1 AND task_status = $2 AND task_name LIKE $3
-                    ORDER BY created_at DESC
-                    "#,
-            )
-            .bind(user_id)
-            .bind(task_status)
-            .bind(search)
-            .fetch_all(&pool)
-            .await
-            .map_err(|_| TaskError::InternalServerError)?
-        }
-        (Some(search), None) => {
-            let search = format!("%{}%", search);
-            sqlx::query_as(
-                r#"
-                        SELECT id, task_name, task_status, created_at, user_id
-                        FROM tasks
-                        WHERE user_id = $1 AND task_name ILIKE $2
-                        ORDER BY created_at DESC
-                        "#,
-            )
-            .bind(user_id)
-            .bind(search)
-            .fetch_all(&pool)
-            .await
-            .map_err(|_| TaskError::InternalServerError)?
+    let TaskFilterDto {
+        task_status,
+        search,
+        workspace_id,
+        project_id,
+        shared,
+        assignee,
+        due,
+    } = task_filter_dto;
+    let search = search.map(|search| format!("%{}%", search));
+    let (assignee_id, unassigned) = match assignee {
+        Some(AssigneeFilter::Me) => (Some(user_id), false),
+        Some(AssigneeFilter::User(assignee_id)) => (Some(assignee_id), false),
+        Some(AssigneeFilter::Unassigned) => (None, true),
+        None => (None, false),
+    };
+    let now = chrono::Utc::now();
+    let (due_from, due_before, not_completed) = match due {
+        Some(DueFilter::Today) => {
+            let (timezone,): (String,) = sqlx::query_as("SELECT timezone FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_one(&pool)
+                .await
+                .map_err(|_| TaskError::InternalServerError)?;
+            let today = time::local_day(now, &timezone);
+            (Some(today.start), Some(today.end), false)
         }
-        (None, Some(task_status)) => sqlx::query_as(
-            r#"
-                SELECT id, task_name, task_status, created_at, user_id
-                FROM tasks
-                WHERE user_id = $1 AND task_status = $2
-                ORDER BY created_at DESC
-                "#,
-        )
-        .bind(user_id)
-        .bind(task_status)
-        .fetch_all(&pool)
-        .await
-        .map_err(|_| TaskError::InternalServerError)?,
-        (None, None) => sqlx::query_as(
-            r#"
-                SELECT id, task_name, task_status, created_at, user_id
-                FROM tasks
-                WHERE user_id = $1
-                ORDER BY created_at DESC
-                "#,
-        )
-        .bind(user_id)
-        .fetch_all(&pool)
-        .await
-        .map_err(|_| TaskError::InternalServerError)?,
+        Some(DueFilter::Overdue) => (None, Some(now), true),
+        None => (None, None, false),
     };
 
+    let tasks = sqlx::query_as(
+        r#"
+        SELECT t.id, t.task_name, t.task_status, t.created_at, t.user_id, t.workspace_id,
+               t.project_id, t.assignee_id, t.description, t.description_html,
+               t.due_at
+        FROM tasks t
+        WHERE CASE
+                WHEN $6 THEN EXISTS (
+                    SELECT 1
+                    FROM share_grants g
+                    JOIN users u ON lower(u.email) = lower(g.grantee_email)
+                    WHERE u.id = $1 AND u.verified
+                        AND (g.task_id = t.id OR g.project_id = t.project_id)
+                )
+                ELSE EXISTS (
+                    SELECT 1
+                    FROM workspace_members m
+                    WHERE m.workspace_id = t.workspace_id AND m.user_id = $1
+                )
+            END
+            AND ($2::task_status IS NULL OR t.task_status = $2)
+            AND ($3::TEXT IS NULL OR t.task_name ILIKE $3)
+            AND ($4::INT IS NULL OR t.workspace_id = $4)
+            AND ($5::INT IS NULL OR t.project_id = $5)
+            AND ($7::INT IS NULL OR t.assignee_id = $7)
+            AND (NOT $8 OR t.assignee_id IS NULL)
+            AND ($9::TIMESTAMPTZ IS NULL OR t.due_at >= $9)
+            AND ($10::TIMESTAMPTZ IS NULL OR t.due_at < $10)
+            AND (NOT $11 OR t.task_status <> 'completed')
+        ORDER BY t.created_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(task_status)
+    .bind(search)
+    .bind(workspace_id)
+    .bind(project_id)
+    .bind(shared)
+    .bind(assignee_id)
+    .bind(unassigned)
+    .bind(due_from)
+    .bind(due_before)
+    .bind(not_completed)
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| TaskError::InternalServerError)?;
+
     Ok(Json(tasks))
 }
 
 pub async fn get_task_by_id(
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
     Path(id): Path<i32>,
 ) -> Result<Json<Task>, TaskError> {
     let user_id = claims.sub;
+    TaskService::require_permission(&pool, id, user_id, TaskPermission::View).await?;
 
     let task = sqlx::query_as(
         r#"
-        SELECT id, task_name, task_status, created_at, user_id
+        SELECT id, task_name, task_status, created_at, user_id, workspace_id, project_id,
+               assignee_id, description, description_html, due_at
         FROM tasks
-        WHERE id = $1 AND user_id = $2
+        WHERE id = $1
         "#,
     )
     .bind(id)
-    .bind(user_id)
     .fetch_one(&pool)
     .await
     .map_err(|_| TaskError::NotFound)?;
@@ -104,29 +145,70 @@ pub async fn get_task_by_id(
 pub async fn create_task(
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
     Json(create_task_dto): Json<CreateTaskDto>,
 ) -> Result<Json<Task>, TaskError> {
     let CreateTaskDto {
         task_name,
         task_status,
+        workspace_id,
+        project_id,
+        description,
+        due_at,
     } = create_task_dto;
     let created_at = chrono::Utc::now();
     let user_id = claims.sub;
 
-    let task = sqlx::query_as(
+    let project_workspace_id = match project_id {
+        Some(project_id) => {
+            let project: Option<(i32,)> =
+                sqlx::query_as("SELECT workspace_id FROM projects WHERE id = $1")
+                    .bind(project_id)
+                    .fetch_optional(&pool)
+                    .await
+                    .map_err(|_| TaskError::InternalServerError)?;
+            Some(project.ok_or(TaskError::BadRequest)?.0)
+        }
+        None => None,
+    };
+    let workspace_id = match (workspace_id, project_workspace_id) {
+        (Some(workspace_id), Some(project_workspace_id))
+            if workspace_id != project_workspace_id =>
+        {
+            return Err(TaskError::BadRequest);
+        }
+        (Some(workspace_id), _) | (None, Some(workspace_id)) => workspace_id,
+        (None, None) => WorkspaceService::personal_workspace_id(&pool, user_id).await?,
+    };
+    WorkspaceService::require_role(&pool, workspace_id, user_id, WorkspaceRole::Member).await?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+    let (id,): (i32,) = sqlx::query_as(
         r#"
-        INSERT INTO tasks (task_name, task_status, created_at, user_id)
-        VALUES ($1, $2, $3, $4)
-        RETURNING id, task_name, task_status, created_at, user_id
+        INSERT INTO tasks
+            (task_name, task_status, created_at, user_id, workspace_id, project_id, due_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
         "#,
     )
     .bind(task_name)
     .bind(task_status)
     .bind(created_at)
     .bind(user_id)
-    .fetch_one(&pool)
+    .bind(workspace_id)
+    .bind(project_id)
+    .bind(due_at)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|_| TaskError::InternalServerError)?;
+    let (task, mentioned) = TaskService::set_description(&mut tx, id, user_id, description).await?;
+    tx.commit()
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+    MentionService::notify(&pool, id, user_id, &mentioned).await;
 
     Ok(Json(task))
 }
@@ -134,17 +216,19 @@ pub async fn create_task(
 pub async fn delete_task(
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
     Path(id): Path<i32>,
 ) -> Result<Json<String>, TaskError> {
     let user_id = claims.sub;
+    TaskService::require_permission(&pool, id, user_id, TaskPermission::Manage).await?;
+
     sqlx::query(
         r#"
         DELETE FROM tasks
-        WHERE id = $1 AND user_id = $2
+        WHERE id = $1
         "#,
     )
     .bind(id)
-    .bind(user_id)
     .execute(&pool)
     .await
     .map_err(|_| TaskError::NotFound)?;
@@ -155,26 +239,128 @@ pub async fn delete_task(
 pub async fn update_task_status(
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
     Path(id): Path<i32>,
     Json(update_task_status_dto): Json<UpdateTaskStatusDto>,
 ) -> Result<Json<Task>, TaskError> {
     let UpdateTaskStatusDto { task_status } = update_task_status_dto;
     let user_id = claims.sub;
+    TaskService::require_permission(&pool, id, user_id, TaskPermission::Edit).await?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+    let (previous,): (TaskStatus,) =
+        sqlx::query_as("SELECT task_status FROM tasks WHERE id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|_| TaskError::InternalServerError)?
+            .ok_or(TaskError::NotFound)?;
 
     let task = sqlx::query_as(
         r#"
         UPDATE tasks
         SET task_status = $1
-        WHERE id = $2 AND user_id = $3
-        RETURNING id, task_name, task_status, created_at, user_id
+        WHERE id = $2
+        RETURNING id, task_name, task_status, created_at, user_id, workspace_id, project_id,
+                  assignee_id, description, description_html, due_at
         "#,
     )
-    .bind(task_status)
+    .bind(&task_status)
     .bind(id)
-    .bind(user_id)
-    .fetch_one(&pool)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|_| TaskError::InternalServerError)?;
 
+    if previous != task_status {
+        TaskService::record_history(
+            &mut tx,
+            id,
+            user_id,
+            TaskEvent::StatusChanged,
+            json!(previous),
+            json!(task_status),
+        )
+        .await?;
+    }
+    tx.commit()
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+
+    Ok(Json(task))
+}
+
+pub async fn update_task_assignee(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+    Json(update_task_assignee_dto): Json<UpdateTaskAssigneeDto>,
+) -> Result<Json<Task>, TaskError> {
+    let task =
+        TaskService::assign_task(&pool, id, claims.sub, update_task_assignee_dto.assignee_id)
+            .await?;
+    Ok(Json(task))
+}
+
+pub async fn get_task_history(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<TaskHistoryEntry>>, TaskError> {
+    let history = TaskService::get_history(&pool, id, claims.sub).await?;
+    Ok(Json(history))
+}
+
+pub async fn update_task_description(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+    Json(update_task_description_dto): Json<UpdateTaskDescriptionDto>,
+) -> Result<Json<Task>, TaskError> {
+    let user_id = claims.sub;
+    TaskService::require_permission(&pool, id, user_id, TaskPermission::Edit).await?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+    let (task, mentioned) = TaskService::set_description(
+        &mut tx,
+        id,
+        user_id,
+        update_task_description_dto.description,
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+    MentionService::notify(&pool, id, user_id, &mentioned).await;
+
+    Ok(Json(task))
+}
+
+pub async fn get_mentions(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+) -> Result<Json<Vec<Mention>>, TaskError> {
+    let mentions = MentionService::get_mentions(&pool, claims.sub).await?;
+    Ok(Json(mentions))
+}
+
+pub async fn update_task_due_date(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+    Json(update_task_due_date_dto): Json<UpdateTaskDueDateDto>,
+) -> Result<Json<Task>, TaskError> {
+    let task =
+        TaskService::set_due_date(&pool, id, claims.sub, update_task_due_date_dto.due_at).await?;
     Ok(Json(task))
 }
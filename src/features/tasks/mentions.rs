@@ -0,0 +1,358 @@
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, PgConnection, PgPool};
+
+use crate::features::notifications::{
+    models::NotificationType,
+    notifier::{NewNotification, Notifier},
+};
+
+use super::models::TaskError;
+
+/// Characters that can appear in a handle or an email after the `@`.
+fn is_handle_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '+' | '@')
+}
+
+/// `@handle` and `@someone@example.com` mentions in `text`, with the byte range
+/// each covers including the leading `@`. An `@` inside a word doesn't start a
+/// mention, so plain email addresses in the text are left alone.
+fn parse_mentions(text: &str) -> Vec<(Range<usize>, &str)> {
+    let mut mentions = Vec::new();
+    let mut from = 0;
+    while let Some(offset) = text[from..].find('@') {
+        let at = from + offset;
+        from = at + 1;
+        if text[..at].chars().next_back().is_some_and(is_handle_char) {
+            continue;
+        }
+        let rest = &text[at + 1..];
+        let len = rest.find(|c| !is_handle_char(c)).unwrap_or(rest.len());
+        let handle = rest[..len].trim_end_matches(&['.', '_', '-', '+', '@'][..]);
+        if !handle.is_empty() {
+            mentions.push((at..at + 1 + handle.len(), handle));
+            from = at + 1 + handle.len();
+        }
+    }
+    mentions
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Text with its mentions resolved: `html` is the escaped text with each
+/// resolved mention turned into a link to the user.
+pub struct MentionedText {
+    pub html: String,
+    pub user_ids: Vec<i32>,
+}
+
+/// Links each mention that matches exactly one of `candidates` (user id and
+/// email): an email must match exactly, a handle must be the local part.
+fn render(
+    text: &str,
+    mentions: Vec<(Range<usize>, &str)>,
+    candidates: &[(i32, String)],
+) -> MentionedText {
+    let mut html = String::with_capacity(text.len());
+    let mut user_ids = Vec::new();
+    let mut last = 0;
+    for (range, handle) in mentions {
+        let mut matches = candidates.iter().filter(|(_, email)| {
+            if handle.contains('@') {
+                email.eq_ignore_ascii_case(handle)
+            } else {
+                email
+                    .split('@')
+                    .next()
+                    .is_some_and(|local| local.eq_ignore_ascii_case(handle))
+            }
+        });
+        let user_id = match (matches.next(), matches.next()) {
+            (Some(&(user_id, _)), None) => user_id,
+            _ => continue,
+        };
+
+        html.push_str(&escape_html(&text[last..range.start]));
+        html.push_str(&format!(
+            r#"<a class="mention" href="http://localhost:3000/users/{}">{}</a>"#,
+            user_id,
+            escape_html(&text[range.clone()])
+        ));
+        last = range.end;
+        if !user_ids.contains(&user_id) {
+            user_ids.push(user_id);
+        }
+    }
+    html.push_str(&escape_html(&text[last..]));
+
+    MentionedText { html, user_ids }
+}
+
+/// A mention of the caller, in a task's description or one of its comments.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Mention {
+    pub id: i32,
+    pub task_id: i32,
+    pub comment_id: Option<i32>,
+    pub mentioned_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct MentionService;
+
+impl MentionService {
+    /// Resolves the mentions in `text` against the users who can see the task.
+    /// Anything that doesn't match exactly one of them stays plain text.
+    pub async fn resolve(
+        conn: &mut PgConnection,
+        task_id: i32,
+        text: &str,
+    ) -> Result<MentionedText, TaskError> {
+        let mentions = parse_mentions(text);
+        if mentions.is_empty() {
+            return Ok(MentionedText {
+                html: escape_html(text),
+                user_ids: Vec::new(),
+            });
+        }
+
+        let candidates: Vec<(i32, String)> = sqlx::query_as(
+            r#"
+            SELECT u.id, u.email
+            FROM users u
+            JOIN tasks t ON t.id = $1
+            WHERE EXISTS (
+                    SELECT 1
+                    FROM workspace_members m
+                    WHERE m.workspace_id = t.workspace_id AND m.user_id = u.id
+                )
+                OR (u.verified AND EXISTS (
+                    SELECT 1
+                    FROM share_grants g
+                    WHERE lower(g.grantee_email) = lower(u.email)
+                        AND (g.task_id = t.id OR g.project_id = t.project_id)
+                ))
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(conn)
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+
+        Ok(render(text, mentions, &candidates))
+    }
+
+    /// Stores mentions in a description (`comment_id` of `None`) or comment and
+    /// returns the users not already mentioned there. Self-mentions are skipped.
+    pub async fn record(
+        conn: &mut PgConnection,
+        task_id: i32,
+        comment_id: Option<i32>,
+        mentioned_by: i32,
+        user_ids: &[i32],
+    ) -> Result<Vec<i32>, TaskError> {
+        let user_ids: Vec<i32> = user_ids
+            .iter()
+            .copied()
+            .filter(|&user_id| user_id != mentioned_by)
+            .collect();
+        if user_ids.is_empty() {
+            return Ok(user_ids);
+        }
+
+        let inserted: Vec<(i32,)> = sqlx::query_as(
+            r#"
+            INSERT INTO mentions (task_id, comment_id, user_id, mentioned_by, created_at)
+            SELECT $1, $2, user_id, $4, $5
+            FROM UNNEST($3::INT[]) AS user_id
+            ON CONFLICT DO NOTHING
+            RETURNING user_id
+            "#,
+        )
+        .bind(task_id)
+        .bind(comment_id)
+        .bind(&user_ids)
+        .bind(mentioned_by)
+        .bind(chrono::Utc::now())
+        .fetch_all(conn)
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+        Ok(inserted.into_iter().map(|(user_id,)| user_id).collect())
+    }
+
+    /// Notifies each newly mentioned user with a link to the task.
+    pub async fn notify(pool: &PgPool, task_id: i32, mentioned_by: i32, user_ids: &[i32]) {
+        if user_ids.is_empty() {
+            return;
+        }
+        let context: Result<(String, String), _> = sqlx::query_as(
+            r#"
+            SELECT
+                (SELECT task_name FROM tasks WHERE id = $1),
+                (SELECT email FROM users WHERE id = $2)
+            "#,
+        )
+        .bind(task_id)
+        .bind(mentioned_by)
+        .fetch_one(pool)
+        .await;
+        let (task_name, mentioned_by_email) = match context {
+            Ok(context) => context,
+            Err(err) => {
+                tracing::error!("Failed to look up mention context: {}", err);
+                return;
+            }
+        };
+
+        let notification = NewNotification {
+            notification_type: NotificationType::Mentioned,
+            title: "You were mentioned".to_string(),
+            body: format!("{} mentioned you on \"{}\".", mentioned_by_email, task_name),
+            link: format!("http://localhost:3000/tasks/{}", task_id),
+        };
+        for &user_id in user_ids {
+            Notifier::notify(pool, user_id, &notification).await;
+        }
+    }
+
+    /// Mentions of the user on tasks they can still see, newest first.
+    pub async fn get_mentions(pool: &PgPool, user_id: i32) -> Result<Vec<Mention>, TaskError> {
+        sqlx::query_as(
+            r#"
+            SELECT mn.id, mn.task_id, mn.comment_id, mn.mentioned_by, mn.created_at
+            FROM mentions mn
+            JOIN tasks t ON t.id = mn.task_id
+            JOIN users u ON u.id = mn.user_id
+            WHERE mn.user_id = $1
+                AND (
+                    EXISTS (
+                        SELECT 1
+                        FROM workspace_members m
+                        WHERE m.workspace_id = t.workspace_id AND m.user_id = u.id
+                    )
+                    OR (u.verified AND EXISTS (
+                        SELECT 1
+                        FROM share_grants g
+                        WHERE lower(g.grantee_email) = lower(u.email)
+                            AND (g.task_id = t.id OR g.project_id = t.project_id)
+                    ))
+                )
+            ORDER BY mn.created_at DESC, mn.id DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| TaskError::InternalServerError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handles(text: &str) -> Vec<&str> {
+        parse_mentions(text)
+            .into_iter()
+            .map(|(range, handle)| {
+                assert_eq!(&text[range.start + 1..range.end], handle);
+                handle
+            })
+            .collect()
+    }
+
+    fn candidates() -> Vec<(i32, String)> {
+        vec![
+            (1, "ada@example.com".to_string()),
+            (2, "bob@example.com".to_string()),
+            (3, "bob@example.org".to_string()),
+        ]
+    }
+
+    #[test]
+    fn parses_handles_and_emails() {
+        assert_eq!(
+            handles("hi @ada and @bob@example.org"),
+            ["ada", "bob@example.org"]
+        );
+        assert_eq!(handles("@ada"), ["ada"]);
+        assert_eq!(handles("(@ada)"), ["ada"]);
+    }
+
+    #[test]
+    fn ignores_at_inside_a_word() {
+        assert!(handles("write to ada@example.com").is_empty());
+        assert!(handles("a@b @ @@").is_empty());
+    }
+
+    #[test]
+    fn trims_trailing_punctuation() {
+        assert_eq!(handles("thanks @ada."), ["ada"]);
+        assert_eq!(
+            handles("@ada_, @bob- and @bob@example.org."),
+            ["ada", "bob", "bob@example.org"]
+        );
+    }
+
+    #[test]
+    fn keeps_ranges_on_char_boundaries() {
+        let text = "größe → @jürgen, ça va? 🎉@ada";
+        let mentions = parse_mentions(text);
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(&text[mentions[0].0.clone()], "@jürgen");
+        assert_eq!(&text[mentions[1].0.clone()], "@ada");
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape_html(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn renders_resolved_mentions_as_links() {
+        let text = "<b>@ada</b> & @nobody";
+        let mentioned = render(text, parse_mentions(text), &candidates());
+        assert_eq!(
+            mentioned.html,
+            r#"&lt;b&gt;<a class="mention" href="http://localhost:3000/users/1">@ada</a>&lt;/b&gt; &amp; @nobody"#
+        );
+        assert_eq!(mentioned.user_ids, [1]);
+    }
+
+    #[test]
+    fn escapes_link_text() {
+        let text = "@<ada>";
+        let candidates = [(1, "<ada>".to_string())];
+        let mentioned = render(text, vec![(0..text.len(), "<ada>")], &candidates);
+        assert_eq!(
+            mentioned.html,
+            r#"<a class="mention" href="http://localhost:3000/users/1">@&lt;ada&gt;</a>"#
+        );
+    }
+
+    #[test]
+    fn leaves_ambiguous_handles_as_text() {
+        let text = "@bob or @bob@example.org, @ADA and @ada again";
+        let mentioned = render(text, parse_mentions(text), &candidates());
+        assert!(mentioned.html.starts_with("@bob or <a"));
+        assert_eq!(mentioned.user_ids, [3, 1]);
+    }
+}
@@ -1,3 +1,5 @@
 pub(crate) mod handlers;
+pub mod mentions;
 pub mod models;
 pub mod routes;
+pub mod services;
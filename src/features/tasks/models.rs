@@ -5,27 +5,84 @@ use axum::{
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::prelude::FromRow;
 
+use crate::features::workspaces::models::WorkspaceError;
+
 #[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
 pub struct Task {
     pub id: i32,
     pub task_name: String,
     pub task_status: TaskStatus,
     pub created_at: DateTime<Utc>,
-    pub user_id: i32,
+    /// Who created the task; `None` once their account is deleted.
+    pub user_id: Option<i32>,
+    pub workspace_id: i32,
+    pub project_id: Option<i32>,
+    pub assignee_id: Option<i32>,
+    pub description: Option<String>,
+    /// `description` as HTML, with mentions linked to the users.
+    pub description_html: Option<String>,
+    pub due_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTaskDto {
     pub task_name: String,
     pub task_status: TaskStatus,
+    /// Defaults to the project's workspace, then the caller's personal one.
+    pub workspace_id: Option<i32>,
+    pub project_id: Option<i32>,
+    pub description: Option<String>,
+    pub due_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TaskFilterDto {
     pub task_status: Option<TaskStatus>,
     pub search: Option<String>,
+    pub workspace_id: Option<i32>,
+    pub project_id: Option<i32>,
+    /// `true` lists only tasks shared with the caller instead of those in
+    /// their workspaces.
+    #[serde(default)]
+    pub shared: bool,
+    pub assignee: Option<AssigneeFilter>,
+    pub due: Option<DueFilter>,
+}
+
+/// `due=today` is due during the caller's current day in their time zone;
+/// `due=overdue` is past due and not completed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DueFilter {
+    Today,
+    Overdue,
+}
+
+/// `assignee=me`, `assignee=none` or `assignee=<user id>`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub enum AssigneeFilter {
+    Me,
+    Unassigned,
+    User(i32),
+}
+
+impl TryFrom<String> for AssigneeFilter {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "me" => Ok(AssigneeFilter::Me),
+            "none" => Ok(AssigneeFilter::Unassigned),
+            id => id
+                .parse()
+                .map(AssigneeFilter::User)
+                .map_err(|_| format!("invalid assignee `{}`", value)),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,7 +90,25 @@ pub struct UpdateTaskStatusDto {
     pub task_status: TaskStatus,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, sqlx::Type)]
+/// `null` clears the description.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateTaskDescriptionDto {
+    pub description: Option<String>,
+}
+
+/// `null` clears the due date.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateTaskDueDateDto {
+    pub due_at: Option<DateTime<Utc>>,
+}
+
+/// `null` unassigns the task.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateTaskAssigneeDto {
+    pub assignee_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "task_status", rename_all = "lowercase")]
 pub enum TaskStatus {
     Open,
@@ -41,16 +116,92 @@ pub enum TaskStatus {
     Completed,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "task_event", rename_all = "snake_case")]
+pub enum TaskEvent {
+    StatusChanged,
+    AssigneeChanged,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TaskHistoryEntry {
+    pub id: i32,
+    pub task_id: i32,
+    pub actor_id: Option<i32>,
+    pub event: TaskEvent,
+    pub old_value: Value,
+    pub new_value: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What a user may do with a task, from their role in its workspace or a
+/// share grant. Only workspace members can delete or share a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TaskPermission {
+    View,
+    Edit,
+    Manage,
+}
+
 pub enum TaskError {
     NotFound,
+    Forbidden,
+    BadRequest,
     InternalServerError,
 }
 
+impl From<WorkspaceError> for TaskError {
+    fn from(err: WorkspaceError) -> Self {
+        match err {
+            WorkspaceError::NotFound => TaskError::NotFound,
+            WorkspaceError::Forbidden => TaskError::Forbidden,
+            WorkspaceError::BadRequest | WorkspaceError::Conflict => TaskError::BadRequest,
+            WorkspaceError::InternalServerError => TaskError::InternalServerError,
+        }
+    }
+}
+
 impl IntoResponse for TaskError {
     fn into_response(self) -> Response<Body> {
         match self {
             TaskError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            TaskError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            TaskError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
             TaskError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<AssigneeFilter, String> {
+        AssigneeFilter::try_from(value.to_string())
+    }
+
+    #[test]
+    fn parses_assignee_filters() {
+        assert!(matches!(parse("me"), Ok(AssigneeFilter::Me)));
+        assert!(matches!(parse("none"), Ok(AssigneeFilter::Unassigned)));
+        assert!(matches!(parse("42"), Ok(AssigneeFilter::User(42))));
+    }
+
+    #[test]
+    fn rejects_invalid_assignee_filters() {
+        assert_eq!(parse("bob").unwrap_err(), "invalid assignee `bob`");
+        assert!(parse("").is_err());
+        assert!(parse("Me").is_err());
+        assert!(parse("4.2").is_err());
+        assert!(parse("99999999999").is_err());
+    }
+
+    #[test]
+    fn deserializes_through_try_from() {
+        let filter: AssigneeFilter = serde_json::from_str(r#""me""#).unwrap();
+        assert!(matches!(filter, AssigneeFilter::Me));
+        let err = serde_json::from_str::<AssigneeFilter>(r#""bob""#).unwrap_err();
+        assert!(err.to_string().contains("invalid assignee `bob`"));
+    }
+}
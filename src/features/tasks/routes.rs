@@ -20,6 +20,17 @@ pub fn task_routes(pool: PgPool) -> Router {
             get(handlers::get_task_by_id).delete(handlers::delete_task),
         )
         .route("/tasks/{id}/status", patch(handlers::update_task_status))
-        .layer(middleware::from_fn(jwt_middleware))
+        .route(
+            "/tasks/{id}/assignee",
+            patch(handlers::update_task_assignee),
+        )
+        .route(
+            "/tasks/{id}/description",
+            patch(handlers::update_task_description),
+        )
+        .route("/tasks/{id}/due", patch(handlers::update_task_due_date))
+        .route("/tasks/{id}/history", get(handlers::get_task_history))
+        .route("/mentions", get(handlers::get_mentions))
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
         .with_state(pool)
 }
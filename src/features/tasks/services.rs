@@ -0,0 +1,327 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+
+use crate::{
+    features::{
+        notifications::{
+            models::NotificationType,
+            notifier::{NewNotification, Notifier},
+        },
+        shares::models::SharePermission,
+        workspaces::models::WorkspaceRole,
+    },
+    shared::time,
+};
+
+use super::{
+    mentions::MentionService,
+    models::{Task, TaskError, TaskEvent, TaskHistoryEntry, TaskPermission},
+};
+
+pub struct TaskService;
+
+impl TaskService {
+    /// The higher of what the user's workspace role and any share grant on the
+    /// task or its project allow. `NotFound` when the user can't see the task
+    /// at all, so task ids from other workspaces can't be probed.
+    pub async fn get_permission(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+    ) -> Result<TaskPermission, TaskError> {
+        let access: Option<(Option<WorkspaceRole>, Option<SharePermission>)> = sqlx::query_as(
+            r#"
+            SELECT
+                (
+                    SELECT m.role
+                    FROM workspace_members m
+                    WHERE m.workspace_id = t.workspace_id AND m.user_id = $2
+                ) AS role,
+                (
+                    SELECT MAX(g.permission)
+                    FROM share_grants g
+                    JOIN users u ON lower(u.email) = lower(g.grantee_email)
+                    WHERE u.id = $2 AND u.verified
+                        AND (g.task_id = t.id OR g.project_id = t.project_id)
+                ) AS shared
+            FROM tasks t
+            WHERE t.id = $1
+            "#,
+        )
+        .bind(task_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+        let (role, shared) = access.ok_or(TaskError::NotFound)?;
+
+        let from_role = role.map(|role| {
+            if role.can_edit_tasks() {
+                TaskPermission::Manage
+            } else {
+                TaskPermission::View
+            }
+        });
+        let from_share = shared.map(|permission| match permission {
+            SharePermission::View => TaskPermission::View,
+            SharePermission::Edit => TaskPermission::Edit,
+        });
+        from_role.max(from_share).ok_or(TaskError::NotFound)
+    }
+
+    pub async fn require_permission(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+        required: TaskPermission,
+    ) -> Result<(), TaskError> {
+        if Self::get_permission(pool, task_id, user_id).await? < required {
+            return Err(TaskError::Forbidden);
+        }
+        Ok(())
+    }
+
+    /// Assigns the task to someone who can see it, or unassigns it with
+    /// `None`. The change is recorded in the task's history and the new
+    /// assignee is emailed unless they assigned themselves.
+    pub async fn assign_task(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+        assignee_id: Option<i32>,
+    ) -> Result<Task, TaskError> {
+        Self::require_permission(pool, task_id, user_id, TaskPermission::Edit).await?;
+        if let Some(assignee_id) = assignee_id {
+            Self::get_permission(pool, task_id, assignee_id)
+                .await
+                .map_err(|err| match err {
+                    TaskError::NotFound => TaskError::BadRequest,
+                    err => err,
+                })?;
+        }
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| TaskError::InternalServerError)?;
+        let (previous,): (Option<i32>,) =
+            sqlx::query_as("SELECT assignee_id FROM tasks WHERE id = $1 FOR UPDATE")
+                .bind(task_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|_| TaskError::InternalServerError)?
+                .ok_or(TaskError::NotFound)?;
+        let task: Task = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET assignee_id = $1
+            WHERE id = $2
+            RETURNING id, task_name, task_status, created_at, user_id, workspace_id, project_id,
+                      assignee_id, description, description_html, due_at
+            "#,
+        )
+        .bind(assignee_id)
+        .bind(task_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+        if previous == assignee_id {
+            return Ok(task);
+        }
+        Self::record_history(
+            &mut tx,
+            task_id,
+            user_id,
+            TaskEvent::AssigneeChanged,
+            Value::from(previous),
+            Value::from(assignee_id),
+        )
+        .await?;
+        tx.commit()
+            .await
+            .map_err(|_| TaskError::InternalServerError)?;
+
+        match assignee_id {
+            Some(assignee_id) if assignee_id != user_id => {
+                Self::notify_assignee(pool, &task, assignee_id, user_id).await;
+            }
+            _ => {}
+        }
+        Ok(task)
+    }
+
+    pub async fn get_history(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+    ) -> Result<Vec<TaskHistoryEntry>, TaskError> {
+        Self::require_permission(pool, task_id, user_id, TaskPermission::View).await?;
+
+        sqlx::query_as(
+            r#"
+            SELECT id, task_id, actor_id, event, old_value, new_value, created_at
+            FROM task_history
+            WHERE task_id = $1
+            ORDER BY created_at, id
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| TaskError::InternalServerError)
+    }
+
+    pub async fn record_history(
+        conn: &mut PgConnection,
+        task_id: i32,
+        actor_id: i32,
+        event: TaskEvent,
+        old_value: Value,
+        new_value: Value,
+    ) -> Result<(), TaskError> {
+        sqlx::query(
+            r#"
+            INSERT INTO task_history (task_id, actor_id, event, old_value, new_value, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(task_id)
+        .bind(actor_id)
+        .bind(event)
+        .bind(old_value)
+        .bind(new_value)
+        .bind(chrono::Utc::now())
+        .execute(conn)
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+        Ok(())
+    }
+
+    async fn notify_assignee(pool: &PgPool, task: &Task, assignee_id: i32, assigned_by: i32) {
+        let context: Result<(String, String), _> = sqlx::query_as(
+            r#"
+            SELECT
+                (SELECT email FROM users WHERE id = $1),
+                (SELECT timezone FROM users WHERE id = $2)
+            "#,
+        )
+        .bind(assigned_by)
+        .bind(assignee_id)
+        .fetch_one(pool)
+        .await;
+        let (assigned_by_email, timezone) = match context {
+            Ok(context) => context,
+            Err(err) => {
+                tracing::error!("Failed to look up assignment context: {}", err);
+                return;
+            }
+        };
+
+        let mut body = format!("{} assigned you \"{}\".", assigned_by_email, task.task_name);
+        if let Some(due_at) = task.due_at {
+            body.push_str(&format!(
+                " It's due {}.",
+                time::format_local(due_at, &timezone)
+            ));
+        }
+        let notification = NewNotification {
+            notification_type: NotificationType::Assigned,
+            title: "A task was assigned to you".to_string(),
+            body,
+            link: format!("http://localhost:3000/tasks/{}", task.id),
+        };
+        Notifier::notify(pool, assignee_id, &notification).await;
+    }
+
+    /// Sets the description and its rendered HTML, and records any mentions in
+    /// it. Returns the task and the users mentioned for the first time, to be
+    /// notified once the transaction commits.
+    pub async fn set_description(
+        conn: &mut PgConnection,
+        task_id: i32,
+        user_id: i32,
+        description: Option<String>,
+    ) -> Result<(Task, Vec<i32>), TaskError> {
+        let mentioned = match &description {
+            Some(description) => Some(MentionService::resolve(conn, task_id, description).await?),
+            None => None,
+        };
+        let task = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET description = $1, description_html = $2
+            WHERE id = $3
+            RETURNING id, task_name, task_status, created_at, user_id, workspace_id, project_id,
+                      assignee_id, description, description_html, due_at
+            "#,
+        )
+        .bind(&description)
+        .bind(mentioned.as_ref().map(|mentioned| &mentioned.html))
+        .bind(task_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|_| TaskError::InternalServerError)?
+        .ok_or(TaskError::NotFound)?;
+
+        let newly_mentioned = match mentioned {
+            Some(mentioned) => {
+                MentionService::record(conn, task_id, None, user_id, &mentioned.user_ids).await?
+            }
+            None => Vec::new(),
+        };
+        Ok((task, newly_mentioned))
+    }
+
+    /// Sets or clears the due date. Relative reminders that already went out
+    /// are re-armed when the due date moves them into the future again.
+    pub async fn set_due_date(
+        pool: &PgPool,
+        task_id: i32,
+        user_id: i32,
+        due_at: Option<DateTime<Utc>>,
+    ) -> Result<Task, TaskError> {
+        Self::require_permission(pool, task_id, user_id, TaskPermission::Edit).await?;
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| TaskError::InternalServerError)?;
+        let task = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET due_at = $1
+            WHERE id = $2
+            RETURNING id, task_name, task_status, created_at, user_id, workspace_id, project_id,
+                      assignee_id, description, description_html, due_at
+            "#,
+        )
+        .bind(due_at)
+        .bind(task_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| TaskError::InternalServerError)?;
+        if let Some(due_at) = due_at {
+            sqlx::query(
+                r#"
+                UPDATE task_reminders
+                SET delivered_at = NULL
+                WHERE task_id = $1
+                    AND before_due_minutes IS NOT NULL
+                    AND $2 - make_interval(mins => before_due_minutes) > $3
+                "#,
+            )
+            .bind(task_id)
+            .bind(due_at)
+            .bind(chrono::Utc::now())
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| TaskError::InternalServerError)?;
+        }
+        tx.commit()
+            .await
+            .map_err(|_| TaskError::InternalServerError)?;
+
+        Ok(task)
+    }
+}
@@ -0,0 +1,49 @@
+use sha2::{Digest, Sha256};
+
+/// Largest accepted avatar upload.
+pub const AVATAR_MAX_BYTES: usize = 1024 * 1024;
+
+/// Content type of an uploaded image, judged by its magic bytes rather than
+/// the request headers. Only formats browsers render safely are accepted.
+pub fn sniff_image_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]) {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// SVG avatar with up to two initials on a background colour derived from
+/// `seed`, so it stays the same for a user.
+pub fn initials_avatar(name: &str, seed: &str) -> String {
+    let words: Vec<&str> = name
+        .split(|c: char| c.is_whitespace() || matches!(c, '.' | '_' | '-' | '@'))
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .collect();
+    let initials: String = match words.as_slice() {
+        [] => "?".to_string(),
+        [word] => first_alphanumeric(word).into_iter().collect(),
+        [first, .., last] => [first_alphanumeric(first), first_alphanumeric(last)]
+            .into_iter()
+            .flatten()
+            .collect(),
+    };
+    let hue = u32::from(Sha256::digest(seed.as_bytes())[0]) * 360 / 256;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="128" height="128" viewBox="0 0 128 128"><rect width="128" height="128" fill="hsl({}, 55%, 45%)"/><text x="50%" y="50%" dy=".35em" text-anchor="middle" font-family="sans-serif" font-size="52" fill="#ffffff">{}</text></svg>"##,
+        hue, initials
+    )
+}
+
+fn first_alphanumeric(word: &str) -> Option<char> {
+    word.chars()
+        .find(|c| c.is_alphanumeric())
+        .and_then(|c| c.to_uppercase().next())
+}
@@ -0,0 +1,620 @@
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, PgPool};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{
+    features::{
+        auth::services::AuthService,
+        notifications::models::NotificationType,
+        shares::models::SharePermission,
+        tasks::models::{TaskEvent, TaskStatus},
+        workspaces::models::WorkspaceRole,
+    },
+    shared::{mailer, time},
+};
+
+use super::{
+    models::{DataExportStatus, UserError},
+    preferences::{Preferences, PreferencesService},
+};
+
+/// Bumped whenever a field is renamed or removed; adding fields keeps the version.
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+const DOWNLOAD_LINK_TTL_HOURS: i64 = 24;
+/// A pending export older than this was lost, e.g. to a restart, and is
+/// started again on the next request.
+const PENDING_EXPORT_TIMEOUT_MINUTES: i64 = 30;
+
+/// Everything we hold about a user, written to `export.json` at the root of
+/// the archive. The CSV files hold the same rows for the lists.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataExportV1 {
+    pub format_version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub profile: ExportedProfile,
+    pub tasks: Vec<ExportedTask>,
+    /// Changes to the user's tasks and changes they made to others'.
+    pub task_history: Vec<ExportedTaskEvent>,
+    pub comments: Vec<ExportedComment>,
+    /// Places where the user was mentioned.
+    pub mentions: Vec<ExportedMention>,
+    pub notifications: Vec<ExportedNotification>,
+    pub reminders: Vec<ExportedReminder>,
+    pub workspaces: Vec<ExportedWorkspaceMembership>,
+    pub shares_granted: Vec<ExportedShare>,
+    pub shares_received: Vec<ExportedShare>,
+    pub sessions: Vec<ExportedSession>,
+    /// Token metadata only; the tokens themselves are never stored.
+    pub personal_access_tokens: Vec<ExportedPersonalAccessToken>,
+    /// Accounts at external identity providers linked for sign-in.
+    pub identities: Vec<ExportedIdentity>,
+    pub passkeys: Vec<ExportedPasskey>,
+    /// Effective settings, defaults included.
+    pub preferences: Preferences,
+    /// Written to the archive as its own file.
+    #[serde(skip)]
+    pub avatar: Option<ExportedAvatar>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedProfile {
+    pub id: i32,
+    pub email: String,
+    pub verified: bool,
+    pub totp_enabled: bool,
+    pub display_name: Option<String>,
+    pub timezone: String,
+    pub locale: String,
+    pub has_custom_avatar: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct ExportedAvatar {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedTask {
+    pub id: i32,
+    pub workspace_id: Option<i32>,
+    pub project_id: Option<i32>,
+    pub task_name: String,
+    pub task_status: TaskStatus,
+    pub description: Option<String>,
+    pub assignee_id: Option<i32>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedTaskEvent {
+    pub id: i32,
+    pub task_id: i32,
+    pub actor_id: Option<i32>,
+    pub event: TaskEvent,
+    /// JSON text, so the CSV keeps one column per field.
+    pub old_value: String,
+    pub new_value: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedComment {
+    pub id: i32,
+    pub task_id: i32,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedMention {
+    pub id: i32,
+    pub task_id: i32,
+    pub comment_id: Option<i32>,
+    pub mentioned_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedNotification {
+    pub id: i32,
+    pub notification_type: NotificationType,
+    pub title: String,
+    pub body: String,
+    pub link: String,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedReminder {
+    pub id: i32,
+    pub task_id: i32,
+    pub remind_at: Option<DateTime<Utc>>,
+    pub before_due_minutes: Option<i32>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedWorkspaceMembership {
+    pub workspace_id: i32,
+    pub name: String,
+    pub role: WorkspaceRole,
+    pub personal: bool,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedShare {
+    pub id: i32,
+    pub project_id: Option<i32>,
+    pub task_id: Option<i32>,
+    pub grantee_email: String,
+    pub permission: SharePermission,
+    pub granted_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedSession {
+    pub id: i32,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedPersonalAccessToken {
+    pub id: i32,
+    pub name: String,
+    /// Space-separated, e.g. `tasks:read tasks:write`.
+    pub scopes: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExportedPasskey {
+    pub id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+pub struct DataExportService;
+
+impl DataExportService {
+    /// Queues an export and returns right away; the archive is built in the
+    /// background and the download link is emailed. A pending export is reused
+    /// unless it has been pending so long it must have been lost.
+    pub async fn request_export(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<DataExportStatus, UserError> {
+        let now = Utc::now();
+        sqlx::query(
+            r#"
+            DELETE FROM data_exports
+            WHERE expires_at <= $1
+                OR status = 'failed'
+                OR (status = 'pending' AND created_at <= $2)
+            "#,
+        )
+        .bind(now)
+        .bind(now - chrono::Duration::minutes(PENDING_EXPORT_TIMEOUT_MINUTES))
+        .execute(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        let pending: Option<DataExportStatus> = sqlx::query_as(
+            r#"
+            SELECT id, status, created_at, expires_at
+            FROM data_exports
+            WHERE user_id = $1 AND status = 'pending'
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+        if let Some(pending) = pending {
+            return Ok(pending);
+        }
+
+        let export: DataExportStatus = sqlx::query_as(
+            r#"
+            INSERT INTO data_exports (user_id, status, created_at)
+            VALUES ($1, 'pending', $2)
+            RETURNING id, status, created_at, expires_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(now)
+        .fetch_one(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        tokio::spawn(Self::run_export(pool.clone(), export.id, user_id));
+        Ok(export)
+    }
+
+    pub async fn download(pool: &PgPool, token: &str) -> Result<Vec<u8>, UserError> {
+        let archive: Option<(Vec<u8>,)> = sqlx::query_as(
+            r#"
+            SELECT archive
+            FROM data_exports
+            WHERE download_token_hash = $1 AND status = 'ready' AND expires_at > $2
+            "#,
+        )
+        .bind(AuthService::hash_token(token))
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+        archive.map(|(archive,)| archive).ok_or(UserError::NotFound)
+    }
+
+    async fn run_export(pool: PgPool, export_id: i32, user_id: i32) {
+        if let Err(err) = Self::complete_export(&pool, export_id, user_id).await {
+            tracing::error!("Data export {} failed: {}", export_id, err);
+            let _ = sqlx::query("UPDATE data_exports SET status = 'failed' WHERE id = $1")
+                .bind(export_id)
+                .execute(&pool)
+                .await;
+        }
+    }
+
+    async fn complete_export(pool: &PgPool, export_id: i32, user_id: i32) -> Result<(), String> {
+        let export = Self::collect(pool, user_id).await?;
+        let email = export.profile.email.clone();
+        let timezone = export.profile.timezone.clone();
+        let archive = tokio::task::spawn_blocking(move || Self::build_archive(&export))
+            .await
+            .map_err(|e| e.to_string())??;
+
+        let token = AuthService::generate_verification_token();
+        let expires_at = Utc::now() + chrono::Duration::hours(DOWNLOAD_LINK_TTL_HOURS);
+        sqlx::query(
+            r#"
+            UPDATE data_exports
+            SET status = 'ready', archive = $1, download_token_hash = $2, expires_at = $3
+            WHERE id = $4
+            "#,
+        )
+        .bind(archive)
+        .bind(AuthService::hash_token(&token))
+        .bind(expires_at)
+        .bind(export_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let download_link = format!(
+            "http://localhost:3000/users/exports/download?token={}",
+            token
+        );
+        mailer::send_email(
+            &email,
+            "Your data export is ready",
+            format!(
+                "Download your data before {}: {}",
+                time::format_local(expires_at, &timezone),
+                download_link
+            ),
+        )
+        .await
+    }
+
+    pub async fn collect(pool: &PgPool, user_id: i32) -> Result<DataExportV1, String> {
+        let profile: ExportedProfile = sqlx::query_as(
+            r#"
+            SELECT id, email, COALESCE(verified, FALSE) AS verified, totp_enabled, display_name,
+                   timezone, locale, avatar IS NOT NULL AS has_custom_avatar, created_at
+            FROM users
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let tasks = sqlx::query_as(
+            r#"
+            SELECT id, workspace_id, project_id, task_name, task_status, description,
+                   assignee_id, due_at, created_at
+            FROM tasks
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let task_history = sqlx::query_as(
+            r#"
+            SELECT h.id, h.task_id, h.actor_id, h.event, h.old_value::TEXT AS old_value,
+                   h.new_value::TEXT AS new_value, h.created_at
+            FROM task_history h
+            JOIN tasks t ON t.id = h.task_id
+            WHERE t.user_id = $1 OR h.actor_id = $1
+            ORDER BY h.created_at, h.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let comments = sqlx::query_as(
+            r#"
+            SELECT id, task_id, body, created_at, updated_at
+            FROM task_comments
+            WHERE author_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mentions = sqlx::query_as(
+            r#"
+            SELECT id, task_id, comment_id, mentioned_by, created_at
+            FROM mentions
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let notifications = sqlx::query_as(
+            r#"
+            SELECT id, type AS notification_type, title, body, link, read_at, created_at
+            FROM notifications
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let reminders = sqlx::query_as(
+            r#"
+            SELECT id, task_id, remind_at, before_due_minutes, delivered_at, created_at
+            FROM task_reminders
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let workspaces = sqlx::query_as(
+            r#"
+            SELECT w.id AS workspace_id, w.name, m.role,
+                   w.personal_owner_id IS NOT NULL AS personal, m.created_at AS joined_at
+            FROM workspace_members m
+            JOIN workspaces w ON w.id = m.workspace_id
+            WHERE m.user_id = $1
+            ORDER BY m.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let shares_granted = sqlx::query_as(
+            r#"
+            SELECT id, project_id, task_id, grantee_email, permission, granted_by, created_at
+            FROM share_grants
+            WHERE granted_by = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        // Grants to an unverified email may be meant for someone else.
+        let shares_received = sqlx::query_as(
+            r#"
+            SELECT g.id, g.project_id, g.task_id, g.grantee_email, g.permission, g.granted_by,
+                   g.created_at
+            FROM share_grants g
+            JOIN users u ON lower(u.email) = lower(g.grantee_email)
+            WHERE u.id = $1 AND u.verified
+            ORDER BY g.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let sessions = sqlx::query_as(
+            r#"
+            SELECT id, user_agent, ip_address, created_at, last_seen_at, revoked_at
+            FROM sessions
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let personal_access_tokens = sqlx::query_as(
+            r#"
+            SELECT id, name, array_to_string(scopes, ' ') AS scopes, expires_at, last_used_at,
+                   created_at
+            FROM personal_access_tokens
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let identities = sqlx::query_as(
+            r#"
+            SELECT provider, subject, email, created_at
+            FROM user_identities
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let passkeys = sqlx::query_as(
+            r#"
+            SELECT id, name, created_at, last_used_at
+            FROM webauthn_credentials
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let preferences = PreferencesService::get_preferences(pool, user_id)
+            .await
+            .map_err(|_| "Failed to load preferences".to_string())?;
+
+        let avatar: Option<(Vec<u8>, String)> = sqlx::query_as(
+            r#"
+            SELECT avatar, avatar_content_type
+            FROM users
+            WHERE id = $1 AND avatar IS NOT NULL AND avatar_content_type IS NOT NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(DataExportV1 {
+            format_version: EXPORT_FORMAT_VERSION,
+            generated_at: Utc::now(),
+            profile,
+            tasks,
+            task_history,
+            comments,
+            mentions,
+            notifications,
+            reminders,
+            workspaces,
+            shares_granted,
+            shares_received,
+            sessions,
+            personal_access_tokens,
+            identities,
+            passkeys,
+            preferences,
+            avatar: avatar.map(|(bytes, content_type)| ExportedAvatar {
+                content_type,
+                bytes,
+            }),
+        })
+    }
+
+    /// `export.json` plus one CSV per list, and the avatar if one was uploaded.
+    pub fn build_archive(export: &DataExportV1) -> Result<Vec<u8>, String> {
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+
+        let json = serde_json::to_vec_pretty(export).map_err(|e| e.to_string())?;
+        let files = [
+            ("export.json", json),
+            (
+                "profile.csv",
+                Self::to_csv(std::slice::from_ref(&export.profile))?,
+            ),
+            ("tasks.csv", Self::to_csv(&export.tasks)?),
+            ("task_history.csv", Self::to_csv(&export.task_history)?),
+            ("comments.csv", Self::to_csv(&export.comments)?),
+            ("mentions.csv", Self::to_csv(&export.mentions)?),
+            ("notifications.csv", Self::to_csv(&export.notifications)?),
+            ("reminders.csv", Self::to_csv(&export.reminders)?),
+            ("workspaces.csv", Self::to_csv(&export.workspaces)?),
+            ("shares_granted.csv", Self::to_csv(&export.shares_granted)?),
+            (
+                "shares_received.csv",
+                Self::to_csv(&export.shares_received)?,
+            ),
+            ("sessions.csv", Self::to_csv(&export.sessions)?),
+            (
+                "personal_access_tokens.csv",
+                Self::to_csv(&export.personal_access_tokens)?,
+            ),
+            ("identities.csv", Self::to_csv(&export.identities)?),
+            ("passkeys.csv", Self::to_csv(&export.passkeys)?),
+        ];
+        for (name, contents) in files {
+            zip.start_file(name, options).map_err(|e| e.to_string())?;
+            zip.write_all(&contents).map_err(|e| e.to_string())?;
+        }
+        if let Some(avatar) = &export.avatar {
+            let extension = match avatar.content_type.as_str() {
+                "image/png" => "png",
+                "image/jpeg" => "jpg",
+                "image/gif" => "gif",
+                "image/webp" => "webp",
+                _ => "bin",
+            };
+            zip.start_file(format!("avatar.{}", extension), options)
+                .map_err(|e| e.to_string())?;
+            zip.write_all(&avatar.bytes).map_err(|e| e.to_string())?;
+        }
+
+        zip.finish()
+            .map(|cursor| cursor.into_inner())
+            .map_err(|e| e.to_string())
+    }
+
+    fn to_csv<T: Serialize>(rows: &[T]) -> Result<Vec<u8>, String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for row in rows {
+            writer.serialize(row).map_err(|e| e.to_string())?;
+        }
+        writer.into_inner().map_err(|e| e.to_string())
+    }
+}
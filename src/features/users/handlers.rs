@@ -1,23 +1,198 @@
-use axum::{extract::State, Extension, Json};
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde_json::Value;
 use sqlx::PgPool;
 
-use crate::features::auth::models::Claims;
+use crate::features::auth::{
+    models::Claims,
+    scopes::{RequireScope, RequireSession, UsersRead, UsersWrite},
+};
 
 use super::{
-    models::{User, UserError},
+    export::DataExportService,
+    models::{
+        AccountDeletion, CancelAccountDeletionDto, CreatePersonalAccessTokenDto,
+        CreatedPersonalAccessToken, DataExportStatus, DeleteAccountDto, DownloadDataExportDto,
+        PersonalAccessToken, Session, UpdateProfileDto, UserError, UserProfile,
+    },
+    preferences::{Preferences, PreferencesService},
     services::UserSerivce,
 };
 
 pub async fn get_user(
     State(pool): State<PgPool>,
     Extension(claims): Extension<Claims>,
-) -> Result<Json<User>, UserError> {
+    _scope: RequireScope<UsersRead>,
+) -> Result<Json<UserProfile>, UserError> {
     let user_id = claims.sub;
 
     tracing::info!("User ID: {}", user_id);
 
-    let user = UserSerivce::get_user_by_id(&pool, user_id)
-        .await
-        .map_err(|_| UserError::InternalServerError)?;
+    let user = UserSerivce::get_user_profile(&pool, user_id).await?;
     Ok(Json(user))
 }
+
+pub async fn update_profile(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    Json(update_profile_dto): Json<UpdateProfileDto>,
+) -> Result<Json<UserProfile>, UserError> {
+    let profile = UserSerivce::update_profile(&pool, claims.sub, update_profile_dto).await?;
+    Ok(Json(profile))
+}
+
+pub async fn get_avatar(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersRead>,
+) -> Result<impl IntoResponse, UserError> {
+    let (content_type, avatar) = UserSerivce::get_avatar(&pool, claims.sub).await?;
+    Ok(([(header::CONTENT_TYPE, content_type)], avatar))
+}
+
+pub async fn upload_avatar(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    image: Bytes,
+) -> Result<StatusCode, UserError> {
+    UserSerivce::set_avatar(&pool, claims.sub, &image).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn remove_avatar(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+) -> Result<StatusCode, UserError> {
+    UserSerivce::remove_avatar(&pool, claims.sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_preferences(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersRead>,
+) -> Result<Json<Preferences>, UserError> {
+    let preferences = PreferencesService::get_preferences(&pool, claims.sub).await?;
+    Ok(Json(preferences))
+}
+
+pub async fn update_preferences(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    Json(patch): Json<Value>,
+) -> Result<Json<Preferences>, UserError> {
+    let preferences = PreferencesService::update_preferences(&pool, claims.sub, patch).await?;
+    Ok(Json(preferences))
+}
+
+pub async fn create_personal_access_token(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    _session: RequireSession,
+    Json(create_token_dto): Json<CreatePersonalAccessTokenDto>,
+) -> Result<Json<CreatedPersonalAccessToken>, UserError> {
+    let token = UserSerivce::create_personal_access_token(
+        &pool,
+        claims.sub,
+        &claims.scopes,
+        create_token_dto,
+    )
+    .await?;
+    Ok(Json(token))
+}
+
+pub async fn get_personal_access_tokens(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersRead>,
+) -> Result<Json<Vec<PersonalAccessToken>>, UserError> {
+    let tokens = UserSerivce::get_personal_access_tokens(&pool, claims.sub).await?;
+    Ok(Json(tokens))
+}
+
+pub async fn revoke_personal_access_token(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    _session: RequireSession,
+    Path(id): Path<i32>,
+) -> Result<Json<String>, UserError> {
+    UserSerivce::revoke_personal_access_token(&pool, claims.sub, id).await?;
+    Ok(Json(format!("Token with id {} has been revoked", id)))
+}
+
+pub async fn get_sessions(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersRead>,
+) -> Result<Json<Vec<Session>>, UserError> {
+    let sessions = UserSerivce::get_sessions(&pool, claims.sub, claims.jti.as_deref()).await?;
+    Ok(Json(sessions))
+}
+
+pub async fn revoke_session(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    Path(id): Path<i32>,
+) -> Result<Json<String>, UserError> {
+    UserSerivce::revoke_session(&pool, claims.sub, id).await?;
+    Ok(Json(format!("Session with id {} has been revoked", id)))
+}
+
+pub async fn delete_account(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersWrite>,
+    _session: RequireSession,
+    Json(delete_account_dto): Json<DeleteAccountDto>,
+) -> Result<(StatusCode, Json<AccountDeletion>), UserError> {
+    let deletion =
+        UserSerivce::schedule_account_deletion(&pool, claims.sub, &delete_account_dto.password)
+            .await?;
+    Ok((StatusCode::ACCEPTED, Json(deletion)))
+}
+
+pub async fn cancel_account_deletion(
+    State(pool): State<PgPool>,
+    Json(cancel_account_deletion_dto): Json<CancelAccountDeletionDto>,
+) -> Result<Json<String>, UserError> {
+    UserSerivce::cancel_account_deletion(&pool, &cancel_account_deletion_dto.token).await?;
+    Ok(Json("Account deletion cancelled".to_string()))
+}
+
+pub async fn request_data_export(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<UsersRead>,
+) -> Result<(StatusCode, Json<DataExportStatus>), UserError> {
+    let export = DataExportService::request_export(&pool, claims.sub).await?;
+    Ok((StatusCode::ACCEPTED, Json(export)))
+}
+
+pub async fn download_data_export(
+    State(pool): State<PgPool>,
+    Query(download_data_export_dto): Query<DownloadDataExportDto>,
+) -> Result<impl IntoResponse, UserError> {
+    let archive = DataExportService::download(&pool, &download_data_export_dto.token).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"todo-export.zip\"",
+            ),
+        ],
+        archive,
+    ))
+}
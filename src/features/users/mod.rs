@@ -1,4 +1,7 @@
+pub mod avatar;
+pub mod export;
 pub mod handlers;
 pub mod models;
+pub mod preferences;
 pub mod routes;
 pub mod services;
@@ -2,24 +2,125 @@ use axum::{
     body::Body,
     http::{Response, StatusCode},
     response::IntoResponse,
+    Json,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::prelude::FromRow;
 
-#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+use crate::features::auth::models::{Role, Scope};
+
+/// Credentials used to sign a user in; never returned from a handler, use
+/// `UserProfile` instead.
+#[derive(Debug, Clone, FromRow)]
 pub struct User {
     pub id: i32,
     pub email: String,
     pub password: String,
-    pub created_at: DateTime<Utc>,
     pub verified: bool,
-    pub verification_token: Option<String>,
+    pub role: Role,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub password_reset_required: bool,
+}
+
+/// What a user may see about their own account; has no secret fields.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct UserProfile {
+    pub id: i32,
+    pub email: String,
+    pub verified: bool,
+    pub display_name: Option<String>,
+    /// False while `GET /users/me/avatar` serves generated initials.
+    pub has_custom_avatar: bool,
+    /// IANA name, e.g. `Europe/Berlin`.
+    pub timezone: String,
+    pub locale: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Fields left out are unchanged; an empty `display_name` clears it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateProfileDto {
+    pub display_name: Option<String>,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct PersonalAccessToken {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatePersonalAccessTokenDto {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Returned once on creation; the plain token is never stored or shown again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedPersonalAccessToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub personal_access_token: PersonalAccessToken,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct Session {
+    pub id: i32,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteAccountDto {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelAccountDeletionDto {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountDeletion {
+    pub deletion_scheduled_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DataExportStatus {
+    pub id: i32,
+    /// `pending`, `ready` or `failed`.
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DownloadDataExportDto {
+    pub token: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum UserError {
     NotFound,
+    BadRequest,
+    Forbidden,
+    WrongPassword,
+    InvalidPreferences(String),
+    /// Names of the team workspaces that would be left without an owner.
+    SoleWorkspaceOwner(Vec<String>),
     InternalServerError,
 }
 
@@ -27,7 +128,201 @@ impl IntoResponse for UserError {
     fn into_response(self) -> Response<Body> {
         match self {
             UserError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            UserError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+            UserError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            UserError::WrongPassword => StatusCode::UNAUTHORIZED.into_response(),
+            UserError::InvalidPreferences(reason) => {
+                (StatusCode::BAD_REQUEST, Json(json!({ "error": reason }))).into_response()
+            }
+            UserError::SoleWorkspaceOwner(workspaces) => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "Make someone else an owner of these workspaces, or delete them, first",
+                    "workspaces": workspaces,
+                })),
+            )
+                .into_response(),
             UserError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::features::users::{
+        export::{
+            DataExportV1, ExportedAvatar, ExportedIdentity, ExportedPasskey,
+            ExportedPersonalAccessToken, ExportedProfile, ExportedSession, EXPORT_FORMAT_VERSION,
+        },
+        preferences::Preferences,
+    };
+
+    use super::*;
+
+    /// Columns that hold credentials or secrets derived from them.
+    const SECRET_FIELDS: [&str; 12] = [
+        "password",
+        "totp_secret",
+        "totp_last_step",
+        "token_hash",
+        "code_hash",
+        "recovery_codes",
+        "verification_token",
+        "download_token_hash",
+        "deletion_cancel_token_hash",
+        "public_key",
+        "avatar",
+        "archive",
+    ];
+
+    fn field_names(value: &Value, names: &mut Vec<String>) {
+        match value {
+            Value::Object(fields) => {
+                for (name, value) in fields {
+                    names.push(name.clone());
+                    field_names(value, names);
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| field_names(item, names)),
+            _ => {}
+        }
+    }
+
+    fn assert_no_secrets<T: Serialize>(response: &T) {
+        let value = serde_json::to_value(response).unwrap();
+        let mut names = Vec::new();
+        field_names(&value, &mut names);
+        for name in names {
+            assert!(
+                !SECRET_FIELDS.contains(&name.as_str()),
+                "`{}` is serialized in {}",
+                name,
+                value
+            );
+        }
+    }
+
+    fn personal_access_token() -> PersonalAccessToken {
+        PersonalAccessToken {
+            id: 1,
+            name: "CI".to_string(),
+            scopes: vec![Scope::TasksRead],
+            expires_at: None,
+            last_used_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn user_profile_has_no_secrets() {
+        assert_no_secrets(&UserProfile {
+            id: 1,
+            email: "ada@example.com".to_string(),
+            verified: true,
+            display_name: Some("Ada".to_string()),
+            has_custom_avatar: true,
+            timezone: "Europe/Berlin".to_string(),
+            locale: "en".to_string(),
+            created_at: Utc::now(),
+        });
+    }
+
+    #[test]
+    fn token_responses_have_no_secrets() {
+        assert_no_secrets(&personal_access_token());
+        assert_no_secrets(&vec![personal_access_token()]);
+
+        // The plain token is shown once on creation, but never its hash.
+        let created = CreatedPersonalAccessToken {
+            token: "pat_secret".to_string(),
+            personal_access_token: personal_access_token(),
+        };
+        assert_no_secrets(&created);
+    }
+
+    #[test]
+    fn account_responses_have_no_secrets() {
+        assert_no_secrets(&vec![Session {
+            id: 1,
+            user_agent: Some("curl/8.0".to_string()),
+            ip_address: Some("127.0.0.1".to_string()),
+            created_at: Utc::now(),
+            last_seen_at: Utc::now(),
+            current: true,
+        }]);
+        assert_no_secrets(&AccountDeletion {
+            deletion_scheduled_at: Utc::now(),
+        });
+        assert_no_secrets(&DataExportStatus {
+            id: 1,
+            status: "ready".to_string(),
+            created_at: Utc::now(),
+            expires_at: Some(Utc::now()),
+        });
+        assert_no_secrets(&Preferences::default());
+    }
+
+    #[test]
+    fn data_export_has_no_secrets() {
+        let export = DataExportV1 {
+            format_version: EXPORT_FORMAT_VERSION,
+            generated_at: Utc::now(),
+            profile: ExportedProfile {
+                id: 1,
+                email: "ada@example.com".to_string(),
+                verified: true,
+                totp_enabled: true,
+                display_name: None,
+                timezone: "UTC".to_string(),
+                locale: "en".to_string(),
+                has_custom_avatar: true,
+                created_at: Utc::now(),
+            },
+            tasks: Vec::new(),
+            task_history: Vec::new(),
+            comments: Vec::new(),
+            mentions: Vec::new(),
+            notifications: Vec::new(),
+            reminders: Vec::new(),
+            workspaces: Vec::new(),
+            shares_granted: Vec::new(),
+            shares_received: Vec::new(),
+            sessions: vec![ExportedSession {
+                id: 1,
+                user_agent: None,
+                ip_address: None,
+                created_at: Utc::now(),
+                last_seen_at: Utc::now(),
+                revoked_at: None,
+            }],
+            personal_access_tokens: vec![ExportedPersonalAccessToken {
+                id: 1,
+                name: "CI".to_string(),
+                scopes: "tasks:read".to_string(),
+                expires_at: None,
+                last_used_at: None,
+                created_at: Utc::now(),
+            }],
+            identities: vec![ExportedIdentity {
+                provider: "google".to_string(),
+                subject: "1234".to_string(),
+                email: Some("ada@example.com".to_string()),
+                created_at: Utc::now(),
+            }],
+            passkeys: vec![ExportedPasskey {
+                id: 1,
+                name: "Laptop".to_string(),
+                created_at: Utc::now(),
+                last_used_at: None,
+            }],
+            preferences: Preferences::default(),
+            avatar: Some(ExportedAvatar {
+                content_type: "image/png".to_string(),
+                bytes: vec![0x89, b'P', b'N', b'G'],
+            }),
+        };
+        assert_no_secrets(&export);
+    }
+}
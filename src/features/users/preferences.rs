@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+
+use crate::features::notifications::models::NotificationType;
+
+use super::models::UserError;
+
+/// Bumped whenever a stored key is renamed or removed, with a matching step
+/// in `PreferencesService::upgrade`. Adding keys or changing defaults keeps it.
+pub const PREFERENCES_VERSION: i32 = 1;
+
+/// Client settings. Only the keys a user has changed are stored; everything
+/// else falls back to `Default` when read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preferences {
+    pub default_sort: TaskSort,
+    pub week_start: WeekStart,
+    pub default_project: Option<i32>,
+    pub notifications: NotificationPreferences,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            default_sort: TaskSort::CreatedAtDesc,
+            week_start: WeekStart::Monday,
+            default_project: None,
+            notifications: NotificationPreferences::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskSort {
+    CreatedAtDesc,
+    CreatedAtAsc,
+    NameAsc,
+    NameDesc,
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeekStart {
+    Monday,
+    Sunday,
+    Saturday,
+}
+
+/// `email` and `in_app` switch a channel off for every type; each type can
+/// also be turned off per channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationPreferences {
+    pub email: bool,
+    pub in_app: bool,
+    pub assigned: NotificationChannels,
+    pub mentioned: NotificationChannels,
+    pub commented: NotificationChannels,
+    pub shared: NotificationChannels,
+    pub reminder: NotificationChannels,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            email: true,
+            in_app: true,
+            assigned: NotificationChannels::default(),
+            mentioned: NotificationChannels::default(),
+            commented: NotificationChannels::default(),
+            shared: NotificationChannels::default(),
+            reminder: NotificationChannels::default(),
+        }
+    }
+}
+
+impl NotificationPreferences {
+    /// Where notifications of this type should go.
+    pub fn channels(&self, notification_type: NotificationType) -> NotificationChannels {
+        let channels = match notification_type {
+            NotificationType::Assigned => &self.assigned,
+            NotificationType::Mentioned => &self.mentioned,
+            NotificationType::Commented => &self.commented,
+            NotificationType::Shared => &self.shared,
+            NotificationType::Reminder => &self.reminder,
+        };
+        NotificationChannels {
+            email: self.email && channels.email,
+            in_app: self.in_app && channels.in_app,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationChannels {
+    pub email: bool,
+    pub in_app: bool,
+}
+
+impl Default for NotificationChannels {
+    fn default() -> Self {
+        Self {
+            email: true,
+            in_app: true,
+        }
+    }
+}
+
+pub struct PreferencesService;
+
+impl PreferencesService {
+    pub async fn get_preferences(pool: &PgPool, user_id: i32) -> Result<Preferences, UserError> {
+        let overrides = Self::load_overrides(pool, user_id).await?;
+        Self::resolve(overrides).map_err(|err| {
+            tracing::error!(
+                "Stored preferences for user {} are invalid: {}",
+                user_id,
+                err
+            );
+            UserError::InternalServerError
+        })
+    }
+
+    /// Applies `patch` as a JSON merge patch (RFC 7396) over the stored
+    /// overrides: keys set to `null` go back to their default.
+    pub async fn update_preferences(
+        pool: &PgPool,
+        user_id: i32,
+        patch: Value,
+    ) -> Result<Preferences, UserError> {
+        if !patch.is_object() {
+            return Err(UserError::InvalidPreferences(
+                "Preferences must be a JSON object".to_string(),
+            ));
+        }
+
+        let mut overrides = Self::load_overrides(pool, user_id).await?;
+        Self::merge_patch(&mut overrides, &patch);
+        let preferences =
+            Self::resolve(overrides.clone()).map_err(UserError::InvalidPreferences)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_preferences (user_id, version, preferences, updated_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (user_id)
+            DO UPDATE SET version = $2, preferences = $3, updated_at = NOW()
+            "#,
+        )
+        .bind(user_id)
+        .bind(PREFERENCES_VERSION)
+        .bind(overrides)
+        .execute(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        Ok(preferences)
+    }
+
+    async fn load_overrides(pool: &PgPool, user_id: i32) -> Result<Value, UserError> {
+        let stored: Option<(i32, Value)> =
+            sqlx::query_as("SELECT version, preferences FROM user_preferences WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await
+                .map_err(|_| UserError::InternalServerError)?;
+
+        Ok(match stored {
+            Some((version, overrides)) => Self::upgrade(version, overrides),
+            None => Value::Object(Map::new()),
+        })
+    }
+
+    /// Rewrites overrides saved under an older version into the current shape,
+    /// one version at a time, so old rows never need a database migration.
+    fn upgrade(mut version: i32, overrides: Value) -> Value {
+        while version < PREFERENCES_VERSION {
+            // Each bump of PREFERENCES_VERSION adds the key renames for
+            // `version -> version + 1` here.
+            version += 1;
+        }
+        overrides
+    }
+
+    /// Fills in defaults and validates against the `Preferences` schema.
+    fn resolve(overrides: Value) -> Result<Preferences, String> {
+        serde_json::from_value(overrides).map_err(|err| err.to_string())
+    }
+
+    fn merge_patch(target: &mut Value, patch: &Value) {
+        let Value::Object(patch) = patch else {
+            *target = patch.clone();
+            return;
+        };
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+        let Value::Object(target) = target else {
+            return;
+        };
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                Self::merge_patch(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn merged(target: Value, patch: Value) -> Value {
+        let mut target = target;
+        PreferencesService::merge_patch(&mut target, &patch);
+        target
+    }
+
+    #[test]
+    fn merge_patch_sets_and_keeps_keys() {
+        assert_eq!(
+            merged(
+                json!({ "week_start": "sunday" }),
+                json!({ "default_sort": "name_asc" })
+            ),
+            json!({ "week_start": "sunday", "default_sort": "name_asc" })
+        );
+    }
+
+    #[test]
+    fn merge_patch_null_removes_the_override() {
+        assert_eq!(
+            merged(
+                json!({ "week_start": "sunday", "default_project": 3 }),
+                json!({ "week_start": null, "missing": null })
+            ),
+            json!({ "default_project": 3 })
+        );
+    }
+
+    #[test]
+    fn merge_patch_merges_nested_objects() {
+        let stored =
+            json!({ "notifications": { "email": false, "mentioned": { "in_app": false } } });
+        assert_eq!(
+            merged(
+                stored,
+                json!({ "notifications": { "mentioned": { "email": false }, "email": null } })
+            ),
+            json!({ "notifications": { "mentioned": { "in_app": false, "email": false } } })
+        );
+    }
+
+    #[test]
+    fn merge_patch_replaces_non_objects() {
+        assert_eq!(
+            merged(
+                json!({ "notifications": { "email": false } }),
+                json!({ "notifications": 5 })
+            ),
+            json!({ "notifications": 5 })
+        );
+        assert_eq!(
+            merged(
+                json!({ "default_project": 3 }),
+                json!({ "default_project": { "id": 3 } })
+            ),
+            json!({ "default_project": { "id": 3 } })
+        );
+        assert_eq!(merged(json!({ "a": 1 }), json!([1, 2])), json!([1, 2]));
+    }
+
+    #[test]
+    fn resolve_fills_in_defaults() {
+        let preferences = PreferencesService::resolve(json!({
+            "week_start": "sunday",
+            "notifications": { "commented": { "email": false } }
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&preferences).unwrap(),
+            json!({
+                "default_sort": "created_at_desc",
+                "week_start": "sunday",
+                "default_project": null,
+                "notifications": {
+                    "email": true,
+                    "in_app": true,
+                    "assigned": { "email": true, "in_app": true },
+                    "mentioned": { "email": true, "in_app": true },
+                    "commented": { "email": false, "in_app": true },
+                    "shared": { "email": true, "in_app": true },
+                    "reminder": { "email": true, "in_app": true }
+                }
+            })
+        );
+        let channels = preferences
+            .notifications
+            .channels(NotificationType::Commented);
+        assert!(!channels.email && channels.in_app);
+    }
+
+    #[test]
+    fn resolve_rejects_unknown_keys_and_bad_values() {
+        assert!(PreferencesService::resolve(json!({ "theme": "dark" })).is_err());
+        assert!(PreferencesService::resolve(json!({ "notifications": { "sms": true } })).is_err());
+        assert!(PreferencesService::resolve(
+            json!({ "notifications": { "shared": { "push": true } } })
+        )
+        .is_err());
+        assert!(PreferencesService::resolve(json!({ "week_start": "friday" })).is_err());
+        assert!(PreferencesService::resolve(json!({ "notifications": 5 })).is_err());
+    }
+}
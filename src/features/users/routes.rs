@@ -1,4 +1,8 @@
-use axum::{middleware, routing::get, Router};
+use axum::{
+    middleware,
+    routing::{delete, get, patch, post},
+    Router,
+};
 use sqlx::PgPool;
 
 use crate::features::auth::middlewares::jwt_middleware;
@@ -6,8 +10,45 @@ use crate::features::auth::middlewares::jwt_middleware;
 use super::handlers;
 
 pub fn user_routes(pool: PgPool) -> Router {
+    // Reached from emailed links, so they can't require a token.
+    let public_routes = Router::new()
+        .route(
+            "/users/deletion/cancel",
+            post(handlers::cancel_account_deletion),
+        )
+        .route(
+            "/users/exports/download",
+            get(handlers::download_data_export),
+        );
+
     Router::new()
         .route("/users", get(handlers::get_user))
-        .layer(middleware::from_fn(jwt_middleware))
+        .route(
+            "/users/me",
+            patch(handlers::update_profile).delete(handlers::delete_account),
+        )
+        .route(
+            "/users/me/avatar",
+            get(handlers::get_avatar)
+                .put(handlers::upload_avatar)
+                .delete(handlers::remove_avatar),
+        )
+        .route(
+            "/users/me/tokens",
+            get(handlers::get_personal_access_tokens).post(handlers::create_personal_access_token),
+        )
+        .route(
+            "/users/me/tokens/{id}",
+            delete(handlers::revoke_personal_access_token),
+        )
+        .route(
+            "/users/me/preferences",
+            get(handlers::get_preferences).patch(handlers::update_preferences),
+        )
+        .route("/users/me/export", post(handlers::request_data_export))
+        .route("/users/me/sessions", get(handlers::get_sessions))
+        .route("/users/me/sessions/{id}", delete(handlers::revoke_session))
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
+        .merge(public_routes)
         .with_state(pool)
 }
@@ -1,14 +1,35 @@
-use sqlx::PgPool;
+use std::time::Duration;
 
-use super::models::{User, UserError};
+use sqlx::{PgConnection, PgPool};
+
+use crate::{
+    features::{
+        auth::{models::Scope, services::AuthService},
+        workspaces::services::WorkspaceService,
+    },
+    shared::{mailer, time},
+};
+
+use super::{
+    avatar,
+    models::{
+        AccountDeletion, CreatePersonalAccessTokenDto, CreatedPersonalAccessToken,
+        PersonalAccessToken, Session, UpdateProfileDto, UserError, UserProfile,
+    },
+};
+
+const ACCOUNT_DELETION_GRACE_DAYS: i64 = 14;
+const DISPLAY_NAME_MAX_LENGTH: usize = 100;
+const ACCOUNT_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 pub struct UserSerivce;
 
 impl UserSerivce {
-    pub async fn get_user_by_id(pool: &PgPool, user_id: i32) -> Result<User, UserError> {
+    pub async fn get_user_profile(pool: &PgPool, user_id: i32) -> Result<UserProfile, UserError> {
         let user = sqlx::query_as(
             r#"
-            SELECT id, email, created_at, password
+            SELECT id, email, COALESCE(verified, FALSE) AS verified, display_name,
+                   avatar IS NOT NULL AS has_custom_avatar, timezone, locale, created_at
             FROM users
             WHERE id = $1
             "#,
@@ -19,4 +40,433 @@ impl UserSerivce {
         .map_err(|_| UserError::NotFound)?;
         Ok(user)
     }
+
+    pub async fn update_profile(
+        pool: &PgPool,
+        user_id: i32,
+        update_profile_dto: UpdateProfileDto,
+    ) -> Result<UserProfile, UserError> {
+        let UpdateProfileDto {
+            display_name,
+            timezone,
+            locale,
+        } = update_profile_dto;
+
+        let display_name = display_name.map(|name| name.trim().to_string());
+        if display_name
+            .as_ref()
+            .is_some_and(|name| name.chars().count() > DISPLAY_NAME_MAX_LENGTH)
+        {
+            return Err(UserError::BadRequest);
+        }
+        if timezone
+            .as_deref()
+            .is_some_and(|timezone| time::parse_timezone(timezone).is_none())
+        {
+            return Err(UserError::BadRequest);
+        }
+        if locale
+            .as_deref()
+            .is_some_and(|locale| !Self::is_valid_locale(locale))
+        {
+            return Err(UserError::BadRequest);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET display_name = CASE WHEN $1::TEXT IS NULL THEN display_name ELSE NULLIF($1, '') END,
+                timezone = COALESCE($2, timezone),
+                locale = COALESCE($3, locale)
+            WHERE id = $4
+            "#,
+        )
+        .bind(display_name)
+        .bind(timezone)
+        .bind(locale)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        Self::get_user_profile(pool, user_id).await
+    }
+
+    /// Returns the uploaded avatar, or generated initials when there is none,
+    /// as `(content type, bytes)`.
+    pub async fn get_avatar(pool: &PgPool, user_id: i32) -> Result<(String, Vec<u8>), UserError> {
+        let row: (String, Option<String>, Option<Vec<u8>>, Option<String>) = sqlx::query_as(
+            r#"
+            SELECT email, display_name, avatar, avatar_content_type
+            FROM users
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|_| UserError::NotFound)?;
+
+        match row {
+            (_, _, Some(avatar), Some(content_type)) => Ok((content_type, avatar)),
+            (email, display_name, _, _) => {
+                let name = display_name
+                    .unwrap_or_else(|| email.split('@').next().unwrap_or_default().to_string());
+                let svg = avatar::initials_avatar(&name, &email);
+                Ok(("image/svg+xml".to_string(), svg.into_bytes()))
+            }
+        }
+    }
+
+    pub async fn set_avatar(pool: &PgPool, user_id: i32, image: &[u8]) -> Result<(), UserError> {
+        if image.len() > avatar::AVATAR_MAX_BYTES {
+            return Err(UserError::BadRequest);
+        }
+        let content_type = avatar::sniff_image_type(image).ok_or(UserError::BadRequest)?;
+
+        sqlx::query("UPDATE users SET avatar = $1, avatar_content_type = $2 WHERE id = $3")
+            .bind(image)
+            .bind(content_type)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .map_err(|_| UserError::InternalServerError)?;
+        Ok(())
+    }
+
+    pub async fn remove_avatar(pool: &PgPool, user_id: i32) -> Result<(), UserError> {
+        sqlx::query("UPDATE users SET avatar = NULL, avatar_content_type = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .map_err(|_| UserError::InternalServerError)?;
+        Ok(())
+    }
+
+    /// BCP 47 shape only, e.g. `en`, `de-AT`, `zh-Hant-TW`.
+    fn is_valid_locale(locale: &str) -> bool {
+        let mut subtags = locale.split('-');
+        let language = subtags.next().unwrap_or_default();
+        (2..=3).contains(&language.len())
+            && language.chars().all(|c| c.is_ascii_alphabetic())
+            && subtags.all(|subtag| {
+                (2..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+    }
+
+    /// A token can only carry scopes the caller's own token has.
+    pub async fn create_personal_access_token(
+        pool: &PgPool,
+        user_id: i32,
+        granted_scopes: &[Scope],
+        create_token_dto: CreatePersonalAccessTokenDto,
+    ) -> Result<CreatedPersonalAccessToken, UserError> {
+        let CreatePersonalAccessTokenDto {
+            name,
+            scopes,
+            expires_at,
+        } = create_token_dto;
+        let created_at = chrono::Utc::now();
+
+        if name.trim().is_empty()
+            || scopes.is_empty()
+            || expires_at.is_some_and(|exp| exp <= created_at)
+        {
+            return Err(UserError::BadRequest);
+        }
+        if !scopes.iter().all(|scope| granted_scopes.contains(scope)) {
+            return Err(UserError::Forbidden);
+        }
+
+        let token = AuthService::generate_personal_access_token();
+        let personal_access_token = sqlx::query_as(
+            r#"
+            INSERT INTO personal_access_tokens (user_id, name, token_hash, scopes, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, name, scopes, expires_at, last_used_at, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(name.trim())
+        .bind(AuthService::hash_token(&token))
+        .bind(scopes)
+        .bind(expires_at)
+        .bind(created_at)
+        .fetch_one(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        Ok(CreatedPersonalAccessToken {
+            token,
+            personal_access_token,
+        })
+    }
+
+    pub async fn get_personal_access_tokens(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<Vec<PersonalAccessToken>, UserError> {
+        let tokens = sqlx::query_as(
+            r#"
+            SELECT id, name, scopes, expires_at, last_used_at, created_at
+            FROM personal_access_tokens
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+        Ok(tokens)
+    }
+
+    pub async fn revoke_personal_access_token(
+        pool: &PgPool,
+        user_id: i32,
+        token_id: i32,
+    ) -> Result<(), UserError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM personal_access_tokens
+            WHERE id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(token_id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Active sessions, most recently seen first. `current_jti` marks the
+    /// session of the caller.
+    pub async fn get_sessions(
+        pool: &PgPool,
+        user_id: i32,
+        current_jti: Option<&str>,
+    ) -> Result<Vec<Session>, UserError> {
+        let sessions = sqlx::query_as(
+            r#"
+            SELECT id, user_agent, ip_address, created_at, last_seen_at,
+                   jti IS NOT DISTINCT FROM $2 AS current
+            FROM sessions
+            WHERE user_id = $1 AND revoked_at IS NULL
+            ORDER BY last_seen_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(current_jti)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+        Ok(sessions)
+    }
+
+    pub async fn revoke_session(
+        pool: &PgPool,
+        user_id: i32,
+        session_id: i32,
+    ) -> Result<(), UserError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE sessions
+            SET revoked_at = $3
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Confirms the password and schedules the account for deletion after the
+    /// grace period. The emailed link cancels it until then.
+    pub async fn schedule_account_deletion(
+        pool: &PgPool,
+        user_id: i32,
+        password: &str,
+    ) -> Result<AccountDeletion, UserError> {
+        let user = AuthService::find_user_by_id(pool, user_id)
+            .await
+            .map_err(|_| UserError::NotFound)?;
+        let profile = Self::get_user_profile(pool, user_id).await?;
+        AuthService::verify_password(password, &user.password)
+            .await
+            .map_err(|_| UserError::WrongPassword)?;
+
+        let mut conn = pool
+            .acquire()
+            .await
+            .map_err(|_| UserError::InternalServerError)?;
+        let sole_owned = WorkspaceService::sole_owned_workspaces(&mut conn, user_id)
+            .await
+            .map_err(|_| UserError::InternalServerError)?;
+        if !sole_owned.is_empty() {
+            return Err(UserError::SoleWorkspaceOwner(sole_owned));
+        }
+
+        let token = AuthService::generate_verification_token();
+        let deletion_scheduled_at =
+            chrono::Utc::now() + chrono::Duration::days(ACCOUNT_DELETION_GRACE_DAYS);
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET deletion_scheduled_at = $1, deletion_cancel_token_hash = $2
+            WHERE id = $3
+            "#,
+        )
+        .bind(deletion_scheduled_at)
+        .bind(AuthService::hash_token(&token))
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        let cancel_link = format!(
+            "http://localhost:3000/account/cancel-deletion?token={}",
+            token
+        );
+        let body = format!(
+            "Your account and all of its data will be deleted on {}. \
+             To keep your account, click on the link: {}",
+            time::format_local(deletion_scheduled_at, &profile.timezone),
+            cancel_link
+        );
+        if let Err(err) =
+            mailer::send_email(&user.email, "Your account will be deleted", body).await
+        {
+            tracing::error!("Failed to send account deletion email: {}", err);
+        }
+
+        Ok(AccountDeletion {
+            deletion_scheduled_at,
+        })
+    }
+
+    pub async fn cancel_account_deletion(pool: &PgPool, token: &str) -> Result<(), UserError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET deletion_scheduled_at = NULL, deletion_cancel_token_hash = NULL
+            WHERE deletion_cancel_token_hash = $1 AND deletion_scheduled_at > $2
+            "#,
+        )
+        .bind(AuthService::hash_token(token))
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await
+        .map_err(|_| UserError::InternalServerError)?;
+
+        match result.rows_affected() {
+            0 => Err(UserError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Deletes every account whose grace period is over, one transaction per
+    /// account. An account that fails is logged and left for the next run so
+    /// it can't hold up the rest. Returns how many were deleted.
+    pub async fn purge_deleted_accounts(pool: &PgPool) -> Result<u64, sqlx::Error> {
+        let mut purged = 0;
+        let mut failed: Vec<i32> = Vec::new();
+        loop {
+            let mut tx = pool.begin().await?;
+            let user: Option<(i32, String)> = sqlx::query_as(
+                r#"
+                SELECT id, email
+                FROM users
+                WHERE deletion_scheduled_at <= $1 AND id <> ALL($2)
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+                "#,
+            )
+            .bind(chrono::Utc::now())
+            .bind(&failed)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let Some((user_id, email)) = user else {
+                return Ok(purged);
+            };
+
+            match Self::purge_account(&mut tx, user_id, &email).await {
+                Ok(()) => {
+                    tx.commit().await?;
+                    tracing::info!("Deleted account {}", user_id);
+                    purged += 1;
+                }
+                Err(err) => {
+                    tracing::error!("Failed to delete account {}: {}", user_id, err);
+                    failed.push(user_id);
+                }
+            }
+        }
+    }
+
+    /// Deletes the user with their personal workspace. Tasks they created in
+    /// team workspaces stay there without a creator. Refuses while they're the
+    /// only owner of a team workspace, which they may have become since
+    /// scheduling the deletion.
+    async fn purge_account(
+        conn: &mut PgConnection,
+        user_id: i32,
+        email: &str,
+    ) -> Result<(), String> {
+        let sole_owned = WorkspaceService::sole_owned_workspaces(&mut *conn, user_id)
+            .await
+            .map_err(|err| err.to_string())?;
+        if !sole_owned.is_empty() {
+            return Err(format!(
+                "only owner of team workspaces {}",
+                sole_owned.join(", ")
+            ));
+        }
+
+        for statement in [
+            "DELETE FROM tasks WHERE workspace_id IN \
+             (SELECT id FROM workspaces WHERE personal_owner_id = $1)",
+            "DELETE FROM personal_access_tokens WHERE user_id = $1",
+            "DELETE FROM magic_link_tokens WHERE user_id = $1",
+            "DELETE FROM recovery_codes WHERE user_id = $1",
+            "DELETE FROM sessions WHERE user_id = $1",
+            // Identities, passkeys and anything added later cascade.
+            "DELETE FROM users WHERE id = $1",
+        ] {
+            sqlx::query(statement)
+                .bind(user_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+        sqlx::query("DELETE FROM login_attempts WHERE attempt_key = $1")
+            .bind(format!("account:{}", email.trim().to_lowercase()))
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    pub async fn run_account_purge(pool: PgPool) {
+        let mut interval = tokio::time::interval(ACCOUNT_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = Self::purge_deleted_accounts(&pool).await {
+                tracing::error!("Failed to purge deleted accounts: {}", err);
+            }
+        }
+    }
 }
@@ -0,0 +1,155 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::features::auth::{
+    models::Claims,
+    scopes::{RequireScope, TasksRead, TasksWrite},
+};
+
+use super::{
+    models::{
+        AcceptInvitationDto, CreateInvitationDto, UpdateMemberDto, Workspace, WorkspaceError,
+        WorkspaceInvitation, WorkspaceMember, WorkspaceNameDto,
+    },
+    services::WorkspaceService,
+};
+
+pub async fn get_workspaces(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+) -> Result<Json<Vec<Workspace>>, WorkspaceError> {
+    let workspaces = WorkspaceService::get_workspaces(&pool, claims.sub).await?;
+    Ok(Json(workspaces))
+}
+
+pub async fn create_workspace(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Json(workspace_name_dto): Json<WorkspaceNameDto>,
+) -> Result<Json<Workspace>, WorkspaceError> {
+    let workspace =
+        WorkspaceService::create_workspace(&pool, claims.sub, &workspace_name_dto.name).await?;
+    Ok(Json(workspace))
+}
+
+pub async fn get_workspace(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Path(id): Path<i32>,
+) -> Result<Json<Workspace>, WorkspaceError> {
+    let workspace = WorkspaceService::get_workspace(&pool, id, claims.sub).await?;
+    Ok(Json(workspace))
+}
+
+pub async fn rename_workspace(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+    Json(workspace_name_dto): Json<WorkspaceNameDto>,
+) -> Result<Json<Workspace>, WorkspaceError> {
+    let workspace =
+        WorkspaceService::rename_workspace(&pool, id, claims.sub, &workspace_name_dto.name).await?;
+    Ok(Json(workspace))
+}
+
+pub async fn delete_workspace(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, WorkspaceError> {
+    WorkspaceService::delete_workspace(&pool, id, claims.sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_members(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<WorkspaceMember>>, WorkspaceError> {
+    let members = WorkspaceService::get_members(&pool, id, claims.sub).await?;
+    Ok(Json(members))
+}
+
+pub async fn update_member(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path((id, user_id)): Path<(i32, i32)>,
+    Json(update_member_dto): Json<UpdateMemberDto>,
+) -> Result<StatusCode, WorkspaceError> {
+    WorkspaceService::update_member_role(&pool, id, claims.sub, user_id, update_member_dto.role)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn remove_member(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path((id, user_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, WorkspaceError> {
+    WorkspaceService::remove_member(&pool, id, claims.sub, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn create_invitation(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path(id): Path<i32>,
+    Json(create_invitation_dto): Json<CreateInvitationDto>,
+) -> Result<Json<WorkspaceInvitation>, WorkspaceError> {
+    if create_invitation_dto.validate().is_err() {
+        return Err(WorkspaceError::BadRequest);
+    }
+    let invitation =
+        WorkspaceService::create_invitation(&pool, id, claims.sub, create_invitation_dto).await?;
+    Ok(Json(invitation))
+}
+
+pub async fn get_invitations(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksRead>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<WorkspaceInvitation>>, WorkspaceError> {
+    let invitations = WorkspaceService::get_invitations(&pool, id, claims.sub).await?;
+    Ok(Json(invitations))
+}
+
+pub async fn revoke_invitation(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Path((id, invitation_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, WorkspaceError> {
+    WorkspaceService::revoke_invitation(&pool, id, claims.sub, invitation_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn accept_invitation(
+    State(pool): State<PgPool>,
+    Extension(claims): Extension<Claims>,
+    _scope: RequireScope<TasksWrite>,
+    Json(accept_invitation_dto): Json<AcceptInvitationDto>,
+) -> Result<Json<Workspace>, WorkspaceError> {
+    let workspace = WorkspaceService::accept_invitation(
+        &pool,
+        claims.sub,
+        &claims.email,
+        &accept_invitation_dto.token,
+    )
+    .await?;
+    Ok(Json(workspace))
+}
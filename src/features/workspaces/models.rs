@@ -0,0 +1,106 @@
+use axum::{
+    body::Body,
+    http::{Response, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use validator::Validate;
+
+/// Declared from least to most privileged, so roles compare with `>=`.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "workspace_role", rename_all = "lowercase")]
+pub enum WorkspaceRole {
+    Viewer,
+    Member,
+    Admin,
+    Owner,
+}
+
+impl WorkspaceRole {
+    pub fn can_edit_tasks(self) -> bool {
+        self >= WorkspaceRole::Member
+    }
+
+    pub fn can_manage_members(self) -> bool {
+        self >= WorkspaceRole::Admin
+    }
+}
+
+/// A workspace as seen by one member; `role` is theirs.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct Workspace {
+    pub id: i32,
+    pub name: String,
+    pub personal: bool,
+    pub role: WorkspaceRole,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkspaceNameDto {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WorkspaceMember {
+    pub user_id: i32,
+    pub email: String,
+    pub display_name: Option<String>,
+    pub role: WorkspaceRole,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateMemberDto {
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateInvitationDto {
+    #[validate(email(message = "Invalid email"))]
+    pub email: String,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WorkspaceInvitation {
+    pub id: i32,
+    pub workspace_id: i32,
+    pub email: String,
+    pub role: WorkspaceRole,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcceptInvitationDto {
+    pub token: String,
+}
+
+pub enum WorkspaceError {
+    /// Also returned to non-members, so workspace ids can't be probed.
+    NotFound,
+    Forbidden,
+    BadRequest,
+    Conflict,
+    InternalServerError,
+}
+
+impl IntoResponse for WorkspaceError {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            WorkspaceError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            WorkspaceError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            WorkspaceError::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+            WorkspaceError::Conflict => StatusCode::CONFLICT.into_response(),
+            WorkspaceError::InternalServerError => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
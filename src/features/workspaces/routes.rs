@@ -0,0 +1,43 @@
+use axum::{
+    middleware,
+    routing::{delete, get, patch, post},
+    Router,
+};
+use sqlx::PgPool;
+
+use crate::features::auth::middlewares::jwt_middleware;
+
+use super::handlers;
+
+pub fn workspace_routes(pool: PgPool) -> Router {
+    Router::new()
+        .route(
+            "/workspaces",
+            get(handlers::get_workspaces).post(handlers::create_workspace),
+        )
+        .route(
+            "/workspaces/invitations/accept",
+            post(handlers::accept_invitation),
+        )
+        .route(
+            "/workspaces/{id}",
+            get(handlers::get_workspace)
+                .patch(handlers::rename_workspace)
+                .delete(handlers::delete_workspace),
+        )
+        .route("/workspaces/{id}/members", get(handlers::get_members))
+        .route(
+            "/workspaces/{id}/members/{user_id}",
+            patch(handlers::update_member).delete(handlers::remove_member),
+        )
+        .route(
+            "/workspaces/{id}/invitations",
+            get(handlers::get_invitations).post(handlers::create_invitation),
+        )
+        .route(
+            "/workspaces/{id}/invitations/{invitation_id}",
+            delete(handlers::revoke_invitation),
+        )
+        .layer(middleware::from_fn_with_state(pool.clone(), jwt_middleware))
+        .with_state(pool)
+}
@@ -0,0 +1,569 @@
+use sqlx::{PgConnection, PgPool};
+
+use crate::{features::auth::services::AuthService, shared::mailer};
+
+use super::models::{
+    CreateInvitationDto, Workspace, WorkspaceError, WorkspaceInvitation, WorkspaceMember,
+    WorkspaceRole,
+};
+
+const WORKSPACE_NAME_MAX_LENGTH: usize = 100;
+const INVITATION_TTL_DAYS: i64 = 7;
+
+pub struct WorkspaceService;
+
+impl WorkspaceService {
+    /// Called wherever a user row is inserted, inside the same transaction.
+    pub async fn create_personal_workspace(
+        conn: &mut PgConnection,
+        user_id: i32,
+    ) -> Result<(), sqlx::Error> {
+        let now = chrono::Utc::now();
+        let (workspace_id,): (i32,) = sqlx::query_as(
+            r#"
+            INSERT INTO workspaces (name, personal_owner_id, created_at)
+            VALUES ('Personal', $1, $2)
+            RETURNING id
+            "#,
+        )
+        .bind(user_id)
+        .bind(now)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO workspace_members (workspace_id, user_id, role, created_at)
+            VALUES ($1, $2, 'owner', $3)
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn personal_workspace_id(pool: &PgPool, user_id: i32) -> Result<i32, WorkspaceError> {
+        let workspace: Option<(i32,)> =
+            sqlx::query_as("SELECT id FROM workspaces WHERE personal_owner_id = $1")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await
+                .map_err(|_| WorkspaceError::InternalServerError)?;
+        workspace
+            .map(|(id,)| id)
+            .ok_or(WorkspaceError::InternalServerError)
+    }
+
+    /// Names of the team workspaces the user is the only owner of. Their
+    /// account can't be deleted until someone else owns these, or they're gone.
+    pub async fn sole_owned_workspaces(
+        conn: &mut PgConnection,
+        user_id: i32,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let workspaces: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT w.name
+            FROM workspaces w
+            JOIN workspace_members m ON m.workspace_id = w.id
+            WHERE m.user_id = $1
+                AND m.role = 'owner'
+                AND w.personal_owner_id IS NULL
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM workspace_members o
+                    WHERE o.workspace_id = w.id AND o.role = 'owner' AND o.user_id <> $1
+                )
+            ORDER BY w.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(conn)
+        .await?;
+        Ok(workspaces.into_iter().map(|(name,)| name).collect())
+    }
+
+    /// The user's role in the workspace; `NotFound` if they aren't a member.
+    pub async fn get_role(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+    ) -> Result<WorkspaceRole, WorkspaceError> {
+        let role: Option<(WorkspaceRole,)> = sqlx::query_as(
+            "SELECT role FROM workspace_members WHERE workspace_id = $1 AND user_id = $2",
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        role.map(|(role,)| role).ok_or(WorkspaceError::NotFound)
+    }
+
+    pub async fn require_role(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+        required: WorkspaceRole,
+    ) -> Result<WorkspaceRole, WorkspaceError> {
+        let role = Self::get_role(pool, workspace_id, user_id).await?;
+        if role < required {
+            return Err(WorkspaceError::Forbidden);
+        }
+        Ok(role)
+    }
+
+    pub async fn get_workspaces(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<Vec<Workspace>, WorkspaceError> {
+        sqlx::query_as(
+            r#"
+            SELECT w.id, w.name, w.personal_owner_id IS NOT NULL AS personal, m.role, w.created_at
+            FROM workspaces w
+            JOIN workspace_members m ON m.workspace_id = w.id
+            WHERE m.user_id = $1
+            ORDER BY w.personal_owner_id IS NULL, w.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)
+    }
+
+    pub async fn get_workspace(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+    ) -> Result<Workspace, WorkspaceError> {
+        sqlx::query_as(
+            r#"
+            SELECT w.id, w.name, w.personal_owner_id IS NOT NULL AS personal, m.role, w.created_at
+            FROM workspaces w
+            JOIN workspace_members m ON m.workspace_id = w.id
+            WHERE w.id = $1 AND m.user_id = $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?
+        .ok_or(WorkspaceError::NotFound)
+    }
+
+    pub async fn create_workspace(
+        pool: &PgPool,
+        user_id: i32,
+        name: &str,
+    ) -> Result<Workspace, WorkspaceError> {
+        let name = Self::validate_name(name)?;
+        let now = chrono::Utc::now();
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        let (workspace_id,): (i32,) = sqlx::query_as(
+            "INSERT INTO workspaces (name, created_at) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(name)
+        .bind(now)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        sqlx::query(
+            r#"
+            INSERT INTO workspace_members (workspace_id, user_id, role, created_at)
+            VALUES ($1, $2, 'owner', $3)
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        tx.commit()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+
+        Self::get_workspace(pool, workspace_id, user_id).await
+    }
+
+    pub async fn rename_workspace(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+        name: &str,
+    ) -> Result<Workspace, WorkspaceError> {
+        Self::require_role(pool, workspace_id, user_id, WorkspaceRole::Admin).await?;
+        let name = Self::validate_name(name)?;
+
+        sqlx::query("UPDATE workspaces SET name = $1 WHERE id = $2")
+            .bind(name)
+            .bind(workspace_id)
+            .execute(pool)
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        Self::get_workspace(pool, workspace_id, user_id).await
+    }
+
+    /// Deletes the workspace with its tasks and projects. Personal workspaces
+    /// only go away with their user.
+    pub async fn delete_workspace(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+    ) -> Result<(), WorkspaceError> {
+        let workspace = Self::get_workspace(pool, workspace_id, user_id).await?;
+        if workspace.role != WorkspaceRole::Owner {
+            return Err(WorkspaceError::Forbidden);
+        }
+        if workspace.personal {
+            return Err(WorkspaceError::BadRequest);
+        }
+
+        sqlx::query("DELETE FROM workspaces WHERE id = $1")
+            .bind(workspace_id)
+            .execute(pool)
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        Ok(())
+    }
+
+    pub async fn get_members(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+    ) -> Result<Vec<WorkspaceMember>, WorkspaceError> {
+        Self::get_role(pool, workspace_id, user_id).await?;
+
+        sqlx::query_as(
+            r#"
+            SELECT m.user_id, u.email, u.display_name, m.role, m.created_at
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.workspace_id = $1
+            ORDER BY m.created_at
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)
+    }
+
+    /// Admins manage everyone below owner; only owners can make or unmake
+    /// owners, and the last owner can't step down.
+    pub async fn update_member_role(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+        member_id: i32,
+        role: WorkspaceRole,
+    ) -> Result<(), WorkspaceError> {
+        let caller_role =
+            Self::require_role(pool, workspace_id, user_id, WorkspaceRole::Admin).await?;
+        let member_role = Self::get_role(pool, workspace_id, member_id).await?;
+        Self::ensure_can_manage(caller_role, member_role, role)?;
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        if member_role == WorkspaceRole::Owner && role != WorkspaceRole::Owner {
+            Self::ensure_other_owner(&mut tx, workspace_id, member_id).await?;
+        }
+        sqlx::query(
+            "UPDATE workspace_members SET role = $1 WHERE workspace_id = $2 AND user_id = $3",
+        )
+        .bind(role)
+        .bind(workspace_id)
+        .bind(member_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        tx.commit()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        Ok(())
+    }
+
+    /// Removes a member, or lets any member leave by passing their own id.
+    pub async fn remove_member(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+        member_id: i32,
+    ) -> Result<(), WorkspaceError> {
+        let workspace = Self::get_workspace(pool, workspace_id, user_id).await?;
+        if workspace.personal {
+            return Err(WorkspaceError::BadRequest);
+        }
+        let member_role = Self::get_role(pool, workspace_id, member_id).await?;
+        if member_id != user_id {
+            Self::ensure_can_manage(workspace.role, member_role, member_role)?;
+        }
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        if member_role == WorkspaceRole::Owner {
+            Self::ensure_other_owner(&mut tx, workspace_id, member_id).await?;
+        }
+        sqlx::query("DELETE FROM workspace_members WHERE workspace_id = $1 AND user_id = $2")
+            .bind(workspace_id)
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        tx.commit()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        Ok(())
+    }
+
+    /// Emails an accept link. A new invitation replaces a pending one for the
+    /// same address.
+    pub async fn create_invitation(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+        create_invitation_dto: CreateInvitationDto,
+    ) -> Result<WorkspaceInvitation, WorkspaceError> {
+        let workspace = Self::get_workspace(pool, workspace_id, user_id).await?;
+        let CreateInvitationDto { email, role } = create_invitation_dto;
+        Self::ensure_can_manage(workspace.role, WorkspaceRole::Viewer, role)?;
+        if workspace.personal || role == WorkspaceRole::Owner {
+            return Err(WorkspaceError::BadRequest);
+        }
+
+        let already_member: Option<(i32,)> = sqlx::query_as(
+            r#"
+            SELECT m.user_id
+            FROM workspace_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.workspace_id = $1 AND lower(u.email) = lower($2)
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&email)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        if already_member.is_some() {
+            return Err(WorkspaceError::Conflict);
+        }
+
+        let now = chrono::Utc::now();
+        let token = AuthService::generate_verification_token();
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        sqlx::query(
+            r#"
+            DELETE FROM workspace_invitations
+            WHERE workspace_id = $1 AND lower(email) = lower($2) AND accepted_at IS NULL
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&email)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        let invitation: WorkspaceInvitation = sqlx::query_as(
+            r#"
+            INSERT INTO workspace_invitations
+                (workspace_id, email, role, token_hash, invited_by, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, workspace_id, email, role, expires_at, created_at
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&email)
+        .bind(role)
+        .bind(AuthService::hash_token(&token))
+        .bind(user_id)
+        .bind(now + chrono::Duration::days(INVITATION_TTL_DAYS))
+        .bind(now)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        tx.commit()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+
+        let accept_link = format!(
+            "http://localhost:3000/workspaces/invitations/accept?token={}",
+            token
+        );
+        mailer::send_email(
+            &email,
+            &format!("You've been invited to {}", workspace.name),
+            format!(
+                "You've been invited to join the workspace \"{}\". Sign in or create an \
+                 account with this email address, then accept within {} days: {}",
+                workspace.name, INVITATION_TTL_DAYS, accept_link
+            ),
+        )
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to send workspace invitation: {}", err);
+            WorkspaceError::InternalServerError
+        })?;
+
+        Ok(invitation)
+    }
+
+    pub async fn get_invitations(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+    ) -> Result<Vec<WorkspaceInvitation>, WorkspaceError> {
+        Self::require_role(pool, workspace_id, user_id, WorkspaceRole::Admin).await?;
+
+        sqlx::query_as(
+            r#"
+            SELECT id, workspace_id, email, role, expires_at, created_at
+            FROM workspace_invitations
+            WHERE workspace_id = $1 AND accepted_at IS NULL AND expires_at > $2
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(chrono::Utc::now())
+        .fetch_all(pool)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)
+    }
+
+    pub async fn revoke_invitation(
+        pool: &PgPool,
+        workspace_id: i32,
+        user_id: i32,
+        invitation_id: i32,
+    ) -> Result<(), WorkspaceError> {
+        Self::require_role(pool, workspace_id, user_id, WorkspaceRole::Admin).await?;
+
+        let deleted = sqlx::query(
+            r#"
+            DELETE FROM workspace_invitations
+            WHERE id = $1 AND workspace_id = $2 AND accepted_at IS NULL
+            "#,
+        )
+        .bind(invitation_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        if deleted.rows_affected() == 0 {
+            return Err(WorkspaceError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Only the invited address can accept, so a forwarded link is useless.
+    pub async fn accept_invitation(
+        pool: &PgPool,
+        user_id: i32,
+        email: &str,
+        token: &str,
+    ) -> Result<Workspace, WorkspaceError> {
+        let now = chrono::Utc::now();
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+        let invitation: Option<(i32, WorkspaceRole)> = sqlx::query_as(
+            r#"
+            UPDATE workspace_invitations
+            SET accepted_at = $3
+            WHERE token_hash = $1 AND lower(email) = lower($2)
+                AND accepted_at IS NULL AND expires_at > $3
+            RETURNING workspace_id, role
+            "#,
+        )
+        .bind(AuthService::hash_token(token))
+        .bind(email)
+        .bind(now)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        let (workspace_id, role) = invitation.ok_or(WorkspaceError::NotFound)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO workspace_members (workspace_id, user_id, role, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (workspace_id, user_id) DO NOTHING
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(role)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        tx.commit()
+            .await
+            .map_err(|_| WorkspaceError::InternalServerError)?;
+
+        Self::get_workspace(pool, workspace_id, user_id).await
+    }
+
+    fn validate_name(name: &str) -> Result<&str, WorkspaceError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > WORKSPACE_NAME_MAX_LENGTH {
+            return Err(WorkspaceError::BadRequest);
+        }
+        Ok(name)
+    }
+
+    fn ensure_can_manage(
+        caller: WorkspaceRole,
+        current: WorkspaceRole,
+        new: WorkspaceRole,
+    ) -> Result<(), WorkspaceError> {
+        if !caller.can_manage_members() {
+            return Err(WorkspaceError::Forbidden);
+        }
+        let touches_owner = current == WorkspaceRole::Owner || new == WorkspaceRole::Owner;
+        if touches_owner && caller != WorkspaceRole::Owner {
+            return Err(WorkspaceError::Forbidden);
+        }
+        Ok(())
+    }
+
+    /// Locks the workspace's owner rows until the transaction ends, so two
+    /// owners demoting or removing each other can't both see the other one
+    /// still there.
+    async fn ensure_other_owner(
+        conn: &mut PgConnection,
+        workspace_id: i32,
+        member_id: i32,
+    ) -> Result<(), WorkspaceError> {
+        let owners: Vec<(i32,)> = sqlx::query_as(
+            r#"
+            SELECT user_id
+            FROM workspace_members
+            WHERE workspace_id = $1 AND role = 'owner'
+            FOR UPDATE
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(conn)
+        .await
+        .map_err(|_| WorkspaceError::InternalServerError)?;
+        if owners.iter().all(|&(owner_id,)| owner_id == member_id) {
+            return Err(WorkspaceError::BadRequest);
+        }
+        Ok(())
+    }
+}
@@ -3,9 +3,11 @@ mod features;
 mod shared;
 use axum::Router;
 use config::app_config::AppConfig;
-use features::{auth, tasks, users};
+use features::{
+    admin, auth, comments, notifications, projects, reminders, shares, tasks, users, workspaces,
+};
 use shared::db;
-use std::env;
+use std::{env, net::SocketAddr};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -20,15 +22,33 @@ async fn main() {
 
     let app_config = AppConfig::from_env().expect("Failed to load app config");
     let addr = app_config.get_addr();
+    std::sync::LazyLock::force(&auth::password_policy::PASSWORD_POLICY_CONFIG);
 
     let pool = db::create_db_pool().await;
 
+    tokio::spawn(users::services::UserSerivce::run_account_purge(
+        pool.clone(),
+    ));
+    tokio::spawn(reminders::services::ReminderService::run_reminder_scheduler(pool.clone()));
+
     let app = Router::new()
         .merge(tasks::routes::task_routes(pool.clone()))
+        .merge(comments::routes::comment_routes(pool.clone()))
+        .merge(reminders::routes::reminder_routes(pool.clone()))
+        .merge(projects::routes::project_routes(pool.clone()))
+        .merge(shares::routes::share_routes(pool.clone()))
+        .merge(notifications::routes::notification_routes(pool.clone()))
+        .merge(workspaces::routes::workspace_routes(pool.clone()))
         .merge(users::routes::user_routes(pool.clone()))
+        .merge(admin::routes::admin_routes(pool.clone()))
         .merge(auth::routes::auth_routes(pool));
 
     let listener = tokio::net::TcpListener::bind(addr.as_str()).await.unwrap();
     tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -0,0 +1,31 @@
+use lettre::{transport::stub::StubTransport, Transport};
+
+pub async fn send_email(to: &str, subject: &str, body: String) -> Result<(), String> {
+    let email = lettre::Message::builder()
+        .from("noreply@yourapp.com".parse().unwrap())
+        .to(to
+            .parse()
+            .map_err(|e| format!("Invalid recipient: {}", e))?)
+        .subject(subject)
+        .body(body)
+        .map_err(|e| format!("Failed to build email: {}", e))?;
+
+    // let creds = Credentials::new("smtp_username".to_string(), "smtp_password".to_string());
+
+    // let mailer = lettre::SmtpTransport::relay("smtp.yourapp.com")
+    //     .unwrap()
+    //     .credentials(creds)
+    //     .build();
+
+    // local mailer for testing
+    let mailer = StubTransport::new_ok();
+
+    mailer
+        .send(&email)
+        .map_err(|e| format!("Failed to send email: {}", e))?;
+
+    // local mailer for testing
+    let captured_email = mailer.messages();
+    tracing::info!("Email sent: {:?}", captured_email);
+    Ok(())
+}
@@ -1,2 +1,3 @@
 pub mod db;
-
+pub mod mailer;
+pub mod time;
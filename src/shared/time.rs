@@ -0,0 +1,92 @@
+use std::ops::Range;
+
+use chrono::{DateTime, Days, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Parses an IANA time zone name such as `Europe/Berlin`.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.parse().ok()
+}
+
+/// Formats a timestamp in the user's time zone for emails and other text
+/// shown to them, e.g. `2025-04-01 09:30 CEST`.
+pub fn format_local(at: DateTime<Utc>, timezone: &str) -> String {
+    let timezone = parse_timezone(timezone).unwrap_or(Tz::UTC);
+    at.with_timezone(&timezone)
+        .format("%Y-%m-%d %H:%M %Z")
+        .to_string()
+}
+
+/// The calendar day containing `at` in the user's time zone, as the UTC
+/// instants it starts and ends at. Days around DST changes are 23 or 25 hours.
+pub fn local_day(at: DateTime<Utc>, timezone: &str) -> Range<DateTime<Utc>> {
+    let timezone = parse_timezone(timezone).unwrap_or(Tz::UTC);
+    let today = at.with_timezone(&timezone).date_naive();
+    let start_of = |date: NaiveDate| {
+        // Where midnight is skipped by a DST change the day starts an hour later.
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+        timezone
+            .from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| {
+                timezone
+                    .from_local_datetime(&(midnight + chrono::Duration::hours(1)))
+                    .earliest()
+            })
+            .map_or(midnight.and_utc(), |start| start.with_timezone(&Utc))
+    };
+    let tomorrow = today.checked_add_days(Days::new(1)).unwrap_or(today);
+    start_of(today)..start_of(tomorrow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn local_day_follows_time_zone() {
+        // 23:30 UTC is already the next day in Berlin.
+        let day = local_day(utc("2025-06-01T23:30:00Z"), "Europe/Berlin");
+        assert_eq!(
+            day,
+            utc("2025-06-01T22:00:00Z")..utc("2025-06-02T22:00:00Z")
+        );
+
+        let day = local_day(utc("2025-06-01T23:30:00Z"), "UTC");
+        assert_eq!(
+            day,
+            utc("2025-06-01T00:00:00Z")..utc("2025-06-02T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn local_day_spans_dst_change() {
+        // Clocks go forward on 2025-03-30 in Berlin, so the day is 23 hours.
+        let day = local_day(utc("2025-03-30T12:00:00Z"), "Europe/Berlin");
+        assert_eq!(
+            day,
+            utc("2025-03-29T23:00:00Z")..utc("2025-03-30T22:00:00Z")
+        );
+    }
+
+    #[test]
+    fn local_day_falls_back_to_utc() {
+        let day = local_day(utc("2025-06-01T12:00:00Z"), "Not/AZone");
+        assert_eq!(
+            day,
+            utc("2025-06-01T00:00:00Z")..utc("2025-06-02T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn format_local_uses_zone_abbreviation() {
+        assert_eq!(
+            format_local(utc("2025-04-01T07:30:00Z"), "Europe/Berlin"),
+            "2025-04-01 09:30 CEST"
+        );
+    }
+}